@@ -1,11 +1,359 @@
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
 use std::process::Command;
 
 fn main() {
-    cc::Build::new()
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/api.table");
+    println!("cargo:rerun-if-changed=src/auxbinds.c");
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let (rust, c) = generate(&fs::read_to_string("src/api.table").unwrap());
+    fs::write(Path::new(&out_dir).join("api.rs"), rust).unwrap();
+    fs::write(Path::new(&out_dir).join("api_shims.c"), c).unwrap();
+
+    // the mock backend (src/interface/mock.rs) stands in for both libpython and the C shims
+    if env::var_os("CARGO_FEATURE_MOCK_PYTHON").is_some() {
+        assert!(env::var_os("CARGO_FEATURE_EXTENSION_MODULE").is_none(), "an extension module can't be built against the mock backend");
+        return;
+    }
+    println!("cargo:rerun-if-env-changed=PATH");
+    let mut build = cc::Build::new();
+    for flag in python3_config(&["--includes"]) {
+        build.flag(&flag);
+    }
+    build
         .file("src/auxbinds.c")
+        .file(Path::new(&out_dir).join("api_shims.c"))
         .compile("auxbinds");
-    let ldflags = String::from_utf8(Command::new("python3-config").arg("--ldflags").output().expect("").stdout).unwrap();
-    println!("cargo:rustc-link-arg-bins={}", ldflags);
-    println!("cargo:rustc-link-lib=python3.11");
-    println!("cargo:rustc-link-lib=asan");
+    // an extension module gets the Python symbols from the interpreter that loads it (and can't
+    // bring its own sanitizer runtime), so only the embedding binary links them in
+    for flag in python3_config(&["--ldflags", "--embed"]) {
+        println!("cargo:rustc-link-arg-bins={}", flag);
+    }
+    if env::var_os("CARGO_FEATURE_ASAN").is_some() {
+        println!("cargo:rustc-link-arg-bins=-lasan");
+    }
+}
+
+// the flags of the Python found on PATH, so the build follows whichever one is active
+fn python3_config(args: &[&str]) -> Vec<String> {
+    let output = Command::new("python3-config").args(args).output().expect("python3-config not found");
+    assert!(output.status.success(), "python3-config {} failed", args.join(" "));
+    String::from_utf8(output.stdout).unwrap().split_whitespace().map(String::from).collect()
+}
+
+// an argument of a wrapper: an object (`ty` is None, or one of the `stolen` markers) or a plain value
+struct Param {
+    name: String,
+    ty: Option<String>,
+}
+
+impl Param {
+    fn is_object(&self) -> bool {
+        self.ty.is_none() || self.stolen().is_some()
+    }
+
+    // Some(true) if the reference is stolen even when the call fails, Some(false) if only when it succeeds
+    fn stolen(&self) -> Option<bool> {
+        match self.ty.as_deref() {
+            Some("stolen") => Some(true),
+            Some("stolen on success") => Some(false),
+            _ => None,
+        }
+    }
+
+    fn rust_type(&self) -> &str {
+        if self.is_object() {
+            "PytObjectPointer"
+        } else {
+            self.ty.as_deref().unwrap()
+        }
+    }
+
+    fn ffi_type(&self) -> &str {
+        match self.rust_type() {
+            "&CStr" => "*const libc::c_char",
+            ty => ty,
+        }
+    }
+
+    fn ffi_arg(&self) -> String {
+        match self.rust_type() {
+            "&CStr" => format!("{}.as_ptr()", self.name),
+            _ => self.name.clone(),
+        }
+    }
+}
+
+// one row of src/api.table (see the description there)
+struct Entry {
+    line: usize,
+    wrapper: String,
+    params: Vec<Param>,
+    returns: String,
+    fails: String,
+    c_name: String,
+    c_shim: Option<String>,
+    ghost: String,
+    shadow: String,
+}
+
+fn parse(line: usize, row: &str) -> Entry {
+    let columns: Vec<&str> = row.split('|').map(str::trim).collect();
+    assert!(columns.len() == 6, "src/api.table:{}: expected 6 columns", line);
+    let (wrapper, params) = columns[0].strip_suffix(')').and_then(|sig| sig.split_once('('))
+        .unwrap_or_else(|| panic!("src/api.table:{}: expected `wrapper(args)`", line));
+    let params = params.split(',').map(str::trim).filter(|p| !p.is_empty()).map(|p| match p.split_once(':') {
+        Some((name, ty)) => Param { name: name.trim().to_string(), ty: Some(ty.trim().to_string()) },
+        None => Param { name: p.to_string(), ty: None },
+    }).collect();
+    let (c_name, c_shim) = match columns[3].split_once(':') {
+        Some((name, body)) => (name.trim(), Some(body.trim().to_string())),
+        None => (columns[3], None),
+    };
+    Entry {
+        line,
+        wrapper: wrapper.to_string(),
+        params,
+        returns: columns[1].to_string(),
+        fails: columns[2].to_string(),
+        c_name: c_name.to_string(),
+        c_shim,
+        ghost: columns[4].to_string(),
+        shadow: columns[5].to_string(),
+    }
+}
+
+fn generate(table: &str) -> (String, String) {
+    let entries: Vec<Entry> = table.lines().enumerate()
+        .filter(|(_, row)| !row.trim().is_empty() && !row.starts_with('#'))
+        .map(|(i, row)| parse(i + 1, row))
+        .collect();
+    let mut rust = String::from("// generated by build.rs from src/api.table\n\nextern \"C\" {\n");
+    let mut c = String::from("// generated by build.rs from src/api.table\n\n#include <Python.h>\n");
+    for entry in &entries {
+        let shim_elsewhere = entry.c_shim.is_none() && entries.iter().any(|e| e.c_name == entry.c_name && e.c_shim.is_some());
+        if shim_elsewhere {
+            continue;
+        }
+        let c_returns = if entry.returns == "bool" || entry.returns == "status" { "libc::c_int" } else { "PytObjectPointer" };
+        let params: Vec<String> = entry.params.iter().map(|p| format!("{}: {}", p.name, p.ffi_type())).collect();
+        writeln!(rust, "    fn {}({}) -> {};", entry.c_name, params.join(", "), c_returns).unwrap();
+        if let Some(body) = &entry.c_shim {
+            assert!(entry.params.iter().all(|p| p.ty.is_none()), "src/api.table:{}: shims only take borrowed objects", entry.line);
+            let params: Vec<String> = entry.params.iter().map(|p| format!("PyObject *{}", p.name)).collect();
+            let c_returns = if entry.returns == "bool" { "int " } else { "PyObject *" };
+            write!(c, "\n{}{}({}) {{\n\treturn {};\n}}\n", c_returns, entry.c_name, params.join(", "), body).unwrap();
+        }
+    }
+    rust.push_str("}\n");
+    for entry in &entries {
+        rust.push('\n');
+        rust.push_str(&wrapper(entry));
+    }
+    (rust, c)
+}
+
+// what `s` looks like after a failed call
+fn failure(entry: &Entry) -> String {
+    match entry.fails.as_str() {
+        "never" => panic!("src/api.table:{}: a new object can't be created without failing", entry.line),
+        "any" => "s.error.is_set()".to_string(),
+        exc => format!("(s.error === GpyErrorState::Set(PytExceptionType::{}))", exc),
+    }
+}
+
+fn wrapper(entry: &Entry) -> String {
+    let mut out = String::from("#[trusted]\n");
+    let call = format!("{}({})", entry.c_name, entry.params.iter().map(Param::ffi_arg).collect::<Vec<_>>().join(", "));
+    let mut params: Vec<String> = entry.params.iter().map(|p| format!("{}: {}", p.name, p.rust_type())).collect();
+    let objects = entry.params.iter().filter(|p| p.is_object());
+    assert!(entry.returns == "status" || objects.clone().all(|p| p.stolen().is_none()), "src/api.table:{}: only `status` wrappers steal references", entry.line);
+    match entry.returns.as_str() {
+        "bool" | "borrowed" => {
+            out.push_str("#[pure]\n");
+            for obj in objects {
+                writeln!(out, "#[requires(gpy_ref_held(1, {}))]", obj.name).unwrap();
+            }
+            params.push("s: &GpyGlobalState".to_string());
+            if entry.returns == "bool" {
+                writeln!(out, "#[ensures(result <==> {})]", entry.ghost).unwrap();
+                writeln!(out, "pub unsafe fn {}({}) -> bool {{\n    {} != 0\n}}", entry.wrapper, params.join(", "), call).unwrap();
+            } else {
+                writeln!(out, "#[ensures(result === {})]", entry.ghost).unwrap();
+                writeln!(out, "pub unsafe fn {}({}) -> PytObjectPointer {{\n    {}\n}}", entry.wrapper, params.join(", "), call).unwrap();
+            }
+        }
+        "new" => {
+            assert!(objects.count() == 0, "src/api.table:{}: new references are only made from plain values", entry.line);
+            params.push("s: &mut GpyGlobalState".to_string());
+            out.push_str("#[requires(gpy_gil_held(1))]\n#[ensures(gpy_gil_held(1))]\n");
+            let (kind, ghost) = entry.ghost.split_once(' ')
+                .unwrap_or_else(|| panic!("src/api.table:{}: expected `constant`, `fresh` or `cached`", entry.line));
+            match kind {
+                "constant" => {
+                    assert!(entry.fails == "never", "src/api.table:{}: getting a constant can't fail", entry.line);
+                    write!(out, concat!(
+                        "#[ensures(gpy_ref_held(1, result))]\n",
+                        "#[ensures(result === {obj})]\n",
+                        "#[ensures(constants_preserved(old(s), s))]\n",
+                        "#[ensures(all_other_preserved({obj}, old(s), s))]\n",
+                        "#[ensures({obj}.gpy_get(s).data === {obj}.gpy_get(old(s)).data)]\n",
+                        "#[ensures(ref_count_changed_by({obj}, 1, old(s), s))]\n",
+                        "#[ensures(refs_held_changed_by(1, old(s), s))]\n",
+                        "pub unsafe fn {name}({params}) -> PytObjectPointer {{\n",
+                        "    let obj = {call};\n",
+                        "    #[cfg(feature = \"shadow-heap\")]\n",
+                        "    let before = s.shadow_use(obj);\n",
+                        "    Py_IncRef(obj);\n",
+                        "    #[cfg(feature = \"shadow-heap\")]\n",
+                        "    {{\n",
+                        "        s.shadow_changed_by(obj, before, 1, 0);\n",
+                        "        s.shadow_new_ref(obj, {shadow}, false);\n",
+                        "    }}\n",
+                        "    obj\n",
+                        "}}\n"),
+                        obj = ghost, name = entry.wrapper, params = params.join(", "), call = call, shadow = entry.shadow).unwrap();
+                    return out;
+                }
+                "fresh" => {
+                    out.push_str("#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(old(s)).ref_count == 0) & (result.gpy_get(s).ref_count == 1) & all_other_preserved(result, old(s), s) & !result.gpy_get(s).immortal)]\n");
+                    writeln!(out, "#[ensures(!result.is_null() ==> result.gpy_get(s).data === {} && result.gpy_get(s).lent == 0)]", ghost).unwrap();
+                }
+                // an object that is reused keeps its data, so it must have had the same data before
+                "cached" => {
+                    writeln!(out, "#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & all_other_preserved(result, old(s), s) & ref_count_changed_by(result, 1, old(s), s) & (result.gpy_get(s).data === {}))]", ghost).unwrap();
+                    writeln!(out, "#[ensures(!result.is_null() ==> (result.gpy_get(old(s)).ref_count == 0 || result.gpy_get(old(s)).data === {}) && result.gpy_get(s).lent == result.gpy_get(old(s)).lent)]", ghost).unwrap();
+                }
+                _ => panic!("src/api.table:{}: expected `constant`, `fresh` or `cached`", entry.line),
+            }
+            write!(out, concat!(
+                "#[ensures(result.is_null() ==> all_objects_preserved(old(s), s))]\n",
+                "#[ensures(result.is_null() ==> {failure})]\n",
+                "#[ensures(!result.is_null() ==> errors_preserved(old(s), s))]\n",
+                "#[ensures(constants_preserved(old(s), s))]\n",
+                "#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]\n",
+                "#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]\n",
+                "pub unsafe fn {name}({params}) -> PytObjectPointer {{\n",
+                "    let result = {call};\n",
+                "    #[cfg(feature = \"shadow-heap\")]\n",
+                "    s.shadow_new_ref(result, {shadow}, {fresh});\n",
+                "    result\n",
+                "}}\n"),
+                failure = failure(entry), name = entry.wrapper, params = params.join(", "), call = call,
+                shadow = entry.shadow, fresh = kind == "fresh").unwrap();
+        }
+        "status" => {
+            params.push("s: &mut GpyGlobalState".to_string());
+            out.push_str("#[requires(gpy_gil_held(1))]\n");
+            let objects: Vec<&Param> = objects.collect();
+            for obj in &objects {
+                writeln!(out, "#[requires(gpy_ref_held(1, {}))]", obj.name).unwrap();
+            }
+            for (i, obj0) in objects.iter().enumerate() {
+                for obj1 in &objects[i + 1..] {
+                    writeln!(out, "#[requires({} !== {})]", obj0.name, obj1.name).unwrap();
+                }
+            }
+            let (kind, requires) = entry.ghost.split_once(' ')
+                .unwrap_or_else(|| panic!("src/api.table:{}: expected `setitem` or `unchanged`", entry.line));
+            writeln!(out, "#[requires({})]", requires).unwrap();
+            match kind {
+                "setitem" => out.push_str(&setitem(entry, &objects)),
+                "unchanged" => out.push_str(&unchanged(entry, &objects)),
+                _ => panic!("src/api.table:{}: expected `setitem` or `unchanged`", entry.line),
+            }
+            writeln!(out, "pub unsafe fn {}({}) -> libc::c_int {{", entry.wrapper, params.join(", ")).unwrap();
+            out.push_str(&status_body(entry, &call));
+        }
+        _ => panic!("src/api.table:{}: expected `bool`, `borrowed`, `new` or `status`", entry.line),
+    }
+    out
+}
+
+// the specs of `seq[index] = item`, where the reference to `item` is stolen even if `index` is out of range
+fn setitem(entry: &Entry, objects: &[&Param]) -> String {
+    let (seq, index, item) = match (&entry.params[..], objects) {
+        ([seq, index, item], [_, _]) if seq.stolen().is_none() && index.ty.as_deref() == Some("pyt_ssize_t") && item.stolen() == Some(true) =>
+            (&seq.name, &index.name, &item.name),
+        _ => panic!("src/api.table:{}: `setitem` takes `(seq, index: pyt_ssize_t, item: stolen)`", entry.line),
+    };
+    assert!(!matches!(entry.fails.as_str(), "never" | "any"), "src/api.table:{}: `setitem` fails with the exception for an index out of range", entry.line);
+    let in_range = format!("0 <= {index} && {index} < {seq}.gpy_get(old(s)).data.seq_len()", index = index, seq = seq);
+    let replaced = format!("{}.gpy_get(old(s)).data.seq_item({})", seq, index);
+    format!(concat!(
+        "#[requires({seq}.gpy_get(s).lent == 0)]\n",
+        "#[ensures(gpy_gil_held(1))]\n",
+        "#[ensures(gpy_ref_held(1, {seq}))]\n",
+        "#[ensures(constants_preserved(old(s), s))]\n",
+        "#[ensures({in_range} ==> (result == 0) & errors_preserved(old(s), s))]\n",
+        "#[ensures({in_range} ==> seq_items_preserved_except({seq}, {index}, old(s), s) && {seq}.gpy_get(s).data.seq_item({index}) === {item})]\n",
+        "#[ensures({in_range} ==> {seq}.gpy_get(s).ref_count == {seq}.gpy_get(old(s)).ref_count && {seq}.gpy_get(s).lent == 0)]\n",
+        "#[ensures({in_range} && !{replaced}.is_null() ==>\n",
+        "    all_other_two_preserved({seq}, {replaced}, old(s), s) &&\n",
+        "    ref_count_changed_by({replaced}, -1, old(s), s) &&\n",
+        "    {replaced}.gpy_get(s).lent == {replaced}.gpy_get(old(s)).lent)]\n",
+        "#[ensures({in_range} && {replaced}.is_null() ==> all_other_preserved({seq}, old(s), s))]\n",
+        "#[ensures(!({in_range}) ==> (result == -1) & {failure} & all_other_preserved({item}, old(s), s))]\n",
+        "#[ensures(!({in_range}) ==> ref_count_changed_by({item}, -1, old(s), s) && {item}.gpy_get(s).lent == {item}.gpy_get(old(s)).lent)]\n",
+        "#[ensures(refs_held_changed_by(-1, old(s), s))]\n"),
+        seq = seq, index = index, item = item, in_range = in_range, replaced = replaced, failure = failure(entry))
+}
+
+// the specs of a call that leaves both objects as they are, apart from handing over the reference to
+// the second one when it succeeds
+fn unchanged(entry: &Entry, objects: &[&Param]) -> String {
+    let (obj, value) = match objects {
+        [obj, value] if obj.stolen().is_none() && value.stolen() == Some(false) => (&obj.name, &value.name),
+        _ => panic!("src/api.table:{}: `unchanged` takes an object and then one that is `stolen on success`", entry.line),
+    };
+    format!(concat!(
+        "#[ensures(gpy_gil_held(1))]\n",
+        "#[ensures(gpy_ref_held(1, {obj}))]\n",
+        "#[ensures(result != 0 ==> gpy_ref_held(1, {value}))]\n",
+        "#[ensures(constants_preserved(old(s), s))]\n",
+        "#[ensures({obj}.gpy_get(s) === {obj}.gpy_get(old(s)))]\n",
+        "#[ensures({value}.gpy_get(s) === {value}.gpy_get(old(s)))]\n",
+        "#[ensures(result == 0 ==> errors_preserved(old(s), s))]\n",
+        "#[ensures(result != 0 ==> (result == -1) & {failure})]\n",
+        "#[ensures(all_other_two_preserved({obj}, {value}, old(s), s))]\n",
+        "#[ensures(result == 0 ==> refs_held_changed_by(-1, old(s), s))]\n",
+        "#[ensures(result != 0 ==> refs_held_preserved(old(s), s))]\n"),
+        obj = obj, value = value, failure = failure(entry))
+}
+
+// the call and the shadow heap's bookkeeping for the stolen reference
+fn status_body(entry: &Entry, call: &str) -> String {
+    let stolen = entry.params.iter().find(|p| p.stolen().is_some()).unwrap();
+    if stolen.stolen() == Some(false) {
+        return format!(concat!(
+            "    #[cfg(feature = \"shadow-heap\")]\n",
+            "    s.shadow_use({item});\n",
+            "    let result = {call};\n",
+            "    #[cfg(feature = \"shadow-heap\")]\n",
+            "    {{\n",
+            "        s.shadow_error();\n",
+            "        if result == 0 {{\n",
+            "            s.refs_held -= 1;\n",
+            "        }}\n",
+            "    }}\n",
+            "    result\n",
+            "}}\n"),
+            item = stolen.name, call = call);
+    }
+    // the shadow column names the C functions giving the length of the sequence and its item at an index
+    let (size, get_item) = entry.shadow.split_once(',').map(|(a, b)| (a.trim(), b.trim()))
+        .unwrap_or_else(|| panic!("src/api.table:{}: expected `Size, GetItem` in the shadow column", entry.line));
+    let (seq, index) = (&entry.params[0].name, &entry.params[1].name);
+    format!(concat!(
+        "    #[cfg(feature = \"shadow-heap\")]\n",
+        "    let (before, replaced) = (s.shadow_use({item}), 0 <= {index} && {index} < {size}({seq}) && !{get_item}({seq}, {index}).is_null());\n",
+        "    let result = {call};\n",
+        "    #[cfg(feature = \"shadow-heap\")]\n",
+        "    s.shadow_steal({item}, before, result == 0, replaced);\n",
+        "    result\n",
+        "}}\n"),
+        item = stolen.name, seq = seq, index = index, size = size, get_item = get_item, call = call)
 }
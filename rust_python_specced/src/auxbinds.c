@@ -1,41 +1,110 @@
-#include <python3.11/Python.h>
+#include <Python.h>
 
-int MyLong_CheckExact(PyObject *obj) {
-	return PyLong_CheckExact(obj);
+int My_IsNone(PyObject *obj) {
+	return Py_IsNone(obj);
 }
 
-int MyDict_CheckExact(PyObject *obj) {
-	return PyDict_CheckExact(obj);
+int My_IsTrue(PyObject *obj) {
+	return Py_IsTrue(obj);
 }
 
-PyObject *MyNone_GetNoIncRef() {
-	return Py_None;
+int My_IsFalse(PyObject *obj) {
+	return Py_IsFalse(obj);
 }
 
-PyObject *MyBool_GetTrueNoIncRef() {
-	return Py_True;
+int My_Is(PyObject *obj0, PyObject *obj1) {
+	return Py_Is(obj0, obj1);
 }
 
-PyObject *MyBool_GetFalseNoIncRef() {
-	return Py_False;
+// `Py_REFCNT` is an inline function, not an exported symbol
+Py_ssize_t My_REFCNT(PyObject *obj) {
+	return Py_REFCNT(obj);
 }
 
-int MyBool_Check(PyObject *obj) {
-	return PyBool_Check(obj);
+// what counts as immortal (and the reference count it reports) differs between versions; before
+// 3.12 nothing is
+int My_IsImmortal(PyObject *obj) {
+#if PY_VERSION_HEX >= 0x030C0000
+	return _Py_IsImmortal(obj);
+#else
+	(void)obj;
+	return 0;
+#endif
 }
 
-int My_IsNone(PyObject *obj) {
-	return Py_IsNone(obj);
+PyObject *My_Type(PyObject *obj) {
+	return (PyObject *)Py_TYPE(obj);
 }
 
-int My_IsTrue(PyObject *obj) {
-	return Py_IsTrue(obj);
+int My_IsType(PyObject *obj, PyObject *typ) {
+	return Py_IS_TYPE(obj, (PyTypeObject *)typ);
 }
 
-int My_IsFalse(PyObject *obj) {
-	return Py_IsFalse(obj);
+// the definition has to outlive the module, which in practice lives until the interpreter exits,
+// so neither it nor the method table is ever freed
+PyObject *MyModule_Create(const char *name, const char *doc, const PyMethodDef *methods, Py_ssize_t count) {
+	PyMethodDef *table = PyMem_Calloc(count + 1, sizeof(PyMethodDef));
+	PyModuleDef *def = PyMem_Calloc(1, sizeof(PyModuleDef));
+	if (!table || !def) {
+		PyMem_Free(table);
+		PyMem_Free(def);
+		return PyErr_NoMemory();
+	}
+	memcpy(table, methods, count * sizeof(PyMethodDef));
+	def->m_base = (PyModuleDef_Base)PyModuleDef_HEAD_INIT;
+	def->m_name = name;
+	def->m_doc = doc;
+	def->m_size = -1;
+	def->m_methods = table;
+	return PyModule_Create(def);
 }
 
-int My_Is(PyObject *obj0, PyObject *obj1) {
-	return Py_Is(obj0, obj1);
+// the layout of instances of types created by `pyttype_create`: the Rust value is boxed
+typedef struct {
+	PyObject_HEAD
+	void *data;
+} MyInstance;
+
+// instances can only be created from Rust (through `pytinstance_new`), never from Python, so
+// `data` is always set; the type can't be subclassed either
+PyObject *MyType_FromSlots(const char *name, destructor dealloc, getattrofunc getattro, reprfunc repr, binaryfunc nb_add) {
+	PyType_Slot slots[5];
+	int n = 0;
+	slots[n++] = (PyType_Slot){ Py_tp_dealloc, dealloc };
+	if (getattro)
+		slots[n++] = (PyType_Slot){ Py_tp_getattro, getattro };
+	if (repr)
+		slots[n++] = (PyType_Slot){ Py_tp_repr, repr };
+	if (nb_add)
+		slots[n++] = (PyType_Slot){ Py_nb_add, nb_add };
+	slots[n] = (PyType_Slot){ 0, NULL };
+	PyType_Spec spec = { name, sizeof(MyInstance), 0, Py_TPFLAGS_DEFAULT | Py_TPFLAGS_DISALLOW_INSTANTIATION, slots };
+	return PyType_FromSpec(&spec);
+}
+
+// NULL if `typ` isn't a type object
+destructor MyType_Dealloc(PyObject *typ) {
+	return PyType_Check(typ) ? ((PyTypeObject *)typ)->tp_dealloc : NULL;
+}
+
+const char *MyType_Name(PyObject *typ) {
+	return PyType_Check(typ) ? ((PyTypeObject *)typ)->tp_name : NULL;
+}
+
+PyObject *MyInstance_New(PyObject *typ, void *data) {
+	PyObject *obj = PyType_GenericAlloc((PyTypeObject *)typ, 0);
+	if (obj)
+		((MyInstance *)obj)->data = data;
+	return obj;
+}
+
+void *MyInstance_Data(PyObject *obj) {
+	return ((MyInstance *)obj)->data;
+}
+
+// instances of heap types own a reference to their type
+void MyInstance_Free(PyObject *obj) {
+	PyTypeObject *tp = Py_TYPE(obj);
+	tp->tp_free(obj);
+	Py_DECREF(tp);
 }
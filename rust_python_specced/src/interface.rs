@@ -1,5 +1,20 @@
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use prusti_contracts::*;
 
+pub mod extension;
+pub mod handle;
+#[cfg(feature = "mock-python")]
+mod mock;
+#[cfg(feature = "shadow-heap")]
+mod shadow;
+#[cfg(all(test, feature = "mock-python"))]
+mod tests;
+pub mod types;
+
+#[cfg(feature = "shadow-heap")]
+use shadow::ShadowData;
+
 pub type pyt_ssize_t = isize;
 
 #[repr(C)]
@@ -8,12 +23,69 @@ pub struct PytObjectPointer {
     pub _private: usize
 }
 
-type GpyErrorState = bool;
+// the exception types specs can tell apart; anything else that gets raised is `Other`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PytExceptionType {
+    OverflowError,
+    ZeroDivisionError,
+    MemoryError,
+    TypeError,
+    ValueError,
+    IndexError,
+    KeyError,
+    AttributeError,
+    SystemError,
+    Other,
+}
+
+impl PytExceptionType {
+    #[trusted]
+    #[requires(*self != PytExceptionType::Other)]
+    unsafe fn as_ptr(&self) -> PytObjectPointer {
+        match self {
+            PytExceptionType::OverflowError => PyExc_OverflowError,
+            PytExceptionType::ZeroDivisionError => PyExc_ZeroDivisionError,
+            PytExceptionType::MemoryError => PyExc_MemoryError,
+            PytExceptionType::TypeError => PyExc_TypeError,
+            PytExceptionType::ValueError => PyExc_ValueError,
+            PytExceptionType::IndexError => PyExc_IndexError,
+            PytExceptionType::KeyError => PyExc_KeyError,
+            PytExceptionType::AttributeError => PyExc_AttributeError,
+            PytExceptionType::SystemError => PyExc_SystemError,
+            PytExceptionType::Other => unreachable!(),
+        }
+    }
+}
+
+// the pending exception (the error indicator)
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GpyErrorState {
+    NoError,
+    Set(PytExceptionType),
+}
+
+impl GpyErrorState {
+    #[pure]
+    fn is_set(&self) -> bool {
+        matches!(self, GpyErrorState::Set(_))
+    }
+}
 
 pub struct GpyGlobalState {
     _private: usize, // this represents opaque data (the heap of PyObjects)
     error: GpyErrorState,
+    refs_held: pyt_ssize_t, // spec-only: the total amount of `gpy_ref_held` obligations handed out and not yet given back
     constants: GpyConstantObjects,
+    interpreter: Option<PytInterpreter>, // only in the state `pyt_initialize` handed out, which alone may finalize
+    #[cfg(feature = "shadow-heap")]
+    shadow: shadow::ShadowHeap,
+}
+
+impl GpyGlobalState {
+    #[pure]
+    pub fn owns_interpreter(&self) -> bool {
+        self.interpreter.is_some()
+    }
 }
 
 struct GpyConstantObjects {
@@ -21,6 +93,17 @@ struct GpyConstantObjects {
     py_false: PytObjectPointer,
     py_true: PytObjectPointer,
     py_not_implemented: PytObjectPointer,
+    py_type_type: PytObjectPointer,
+    py_none_type: PytObjectPointer,
+    py_bool_type: PytObjectPointer,
+    py_long_type: PytObjectPointer,
+    py_unicode_type: PytObjectPointer,
+    py_list_type: PytObjectPointer,
+    py_tuple_type: PytObjectPointer,
+    py_dict_type: PytObjectPointer,
+    py_module_type: PytObjectPointer,
+    py_float_type: PytObjectPointer,
+    py_bytes_type: PytObjectPointer,
 }
 
 predicate! {
@@ -29,22 +112,204 @@ predicate! {
     }
 }
 
+// the interpreter that is running: 0 if none is, otherwise a number no other interpreter gets, so
+// handles can tell whether the one their object lives in is still around (a `GpyGlobalState` is
+// only ever handed out by `pyt_initialize`, or by `pyt_gilstate_ensure` while the interpreter is
+// running, so nothing else can use the interpreter before it is initialized or after it is
+// finalized)
+static INTERPRETER: AtomicUsize = AtomicUsize::new(0);
+static INTERPRETERS_STARTED: AtomicUsize = AtomicUsize::new(0);
+
+// the right to initialize the interpreter. There is only one: `PytInterpreter::take` hands it out
+// once, `pyt_initialize` uses it up and `pyt_finalize` gives it back, so the interpreter can't be
+// initialized while it is running. In an extension module, the interpreter that loaded it has it.
+pub struct PytInterpreter {
+    _private: (),
+}
+
+static INTERPRETER_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl PytInterpreter {
+    pub fn take() -> Option<PytInterpreter> {
+        if INTERPRETER_TAKEN.swap(true, Ordering::SeqCst) {
+            None
+        } else {
+            Some(PytInterpreter { _private: () })
+        }
+    }
+}
+
+// records that an interpreter started; false if one was running already
+fn gpy_interpreter_started() -> bool {
+    INTERPRETER_TAKEN.store(true, Ordering::SeqCst);
+    let number = INTERPRETERS_STARTED.fetch_add(1, Ordering::SeqCst) + 1;
+    INTERPRETER.compare_exchange(0, number, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+}
+
+// what the state looks like when `pyt_initialize` or `pyt_gilstate_ensure` hands it out
+predicate! {
+    fn gpy_state_started(s: &GpyGlobalState) -> bool {
+        !s.error.is_set() &&
+        s.refs_held == 0 &&
+        has_value_and_pos_ref_count(s.constants.py_none, GpyObjectData::PyNone, s) &&
+        has_value_and_pos_ref_count(s.constants.py_false, GpyObjectData::PyBool(false), s) &&
+        has_value_and_pos_ref_count(s.constants.py_true, GpyObjectData::PyBool(true), s) &&
+        has_value_and_pos_ref_count(s.constants.py_not_implemented, GpyObjectData::PyNotImplemented, s) &&
+        has_value_and_pos_ref_count(s.constants.py_type_type, GpyObjectData::PyType(GpyTypeImpl::Generic), s) &&
+        has_value_and_pos_ref_count(s.constants.py_none_type, GpyObjectData::PyType(GpyTypeImpl::Generic), s) &&
+        has_value_and_pos_ref_count(s.constants.py_bool_type, GpyObjectData::PyType(GpyTypeImpl::Generic), s) &&
+        has_value_and_pos_ref_count(s.constants.py_long_type, GpyObjectData::PyType(GpyTypeImpl::Long), s) &&
+        has_value_and_pos_ref_count(s.constants.py_unicode_type, GpyObjectData::PyType(GpyTypeImpl::Generic), s) &&
+        has_value_and_pos_ref_count(s.constants.py_list_type, GpyObjectData::PyType(GpyTypeImpl::Generic), s) &&
+        has_value_and_pos_ref_count(s.constants.py_tuple_type, GpyObjectData::PyType(GpyTypeImpl::Generic), s) &&
+        has_value_and_pos_ref_count(s.constants.py_dict_type, GpyObjectData::PyType(GpyTypeImpl::Generic), s) &&
+        has_value_and_pos_ref_count(s.constants.py_module_type, GpyObjectData::PyType(GpyTypeImpl::Generic), s) &&
+        has_value_and_pos_ref_count(s.constants.py_float_type, GpyObjectData::PyType(GpyTypeImpl::Generic), s) &&
+        has_value_and_pos_ref_count(s.constants.py_bytes_type, GpyObjectData::PyType(GpyTypeImpl::Generic), s) &&
+        gpy_no_borrows_active(s)
+    }
+}
+
+#[trusted]
+#[ensures(gpy_state_started(&result))]
+#[ensures(result.owns_interpreter())]
+#[ensures(gpy_gil_held(1))]
+pub fn pyt_initialize(interpreter: PytInterpreter) -> GpyGlobalState {
+    assert!(gpy_interpreter_started(), "the interpreter is already initialized");
+    unsafe {
+        Py_Initialize();
+    }
+    GpyGlobalState { interpreter: Some(interpreter), ..gpy_new_state() }
+}
+
+// the ghost state handed out to code that runs inside the interpreter; only its specs carry meaning
 #[trusted]
-#[ensures(!result.error)]
-#[ensures(has_value_and_pos_ref_count(result.constants.py_none, GpyObjectData::PyNone, &result))]
-#[ensures(has_value_and_pos_ref_count(result.constants.py_false, GpyObjectData::PyBool(false), &result))]
-#[ensures(has_value_and_pos_ref_count(result.constants.py_none, GpyObjectData::PyBool(true), &result))]
-#[ensures(has_value_and_pos_ref_count(result.constants.py_none, GpyObjectData::PyNotImplemented, &result))]
-pub fn gpy_create_state() -> GpyGlobalState {
+fn gpy_new_state() -> GpyGlobalState {
     GpyGlobalState {
         _private: 0,
-        error: false,
+        error: GpyErrorState::NoError,
+        refs_held: 0,
         constants: GpyConstantObjects {
             py_none: PytObjectPointer { _private: 0 },
             py_false: PytObjectPointer { _private: 0 },
             py_true: PytObjectPointer { _private: 0 },
             py_not_implemented: PytObjectPointer { _private: 0 },
+            py_type_type: PytObjectPointer { _private: 0 },
+            py_none_type: PytObjectPointer { _private: 0 },
+            py_bool_type: PytObjectPointer { _private: 0 },
+            py_long_type: PytObjectPointer { _private: 0 },
+            py_unicode_type: PytObjectPointer { _private: 0 },
+            py_list_type: PytObjectPointer { _private: 0 },
+            py_tuple_type: PytObjectPointer { _private: 0 },
+            py_dict_type: PytObjectPointer { _private: 0 },
+            py_module_type: PytObjectPointer { _private: 0 },
+            py_float_type: PytObjectPointer { _private: 0 },
+            py_bytes_type: PytObjectPointer { _private: 0 },
+        },
+        interpreter: None,
+        #[cfg(feature = "shadow-heap")]
+        shadow: Default::default(),
+    }
+}
+
+// the runtime counterparts of the specs (see interface/shadow.rs)
+#[cfg(feature = "shadow-heap")]
+impl GpyGlobalState {
+    // `obj` is about to be passed to CPython; returns its reference count for `shadow_changed_by`
+    #[track_caller]
+    unsafe fn shadow_use(&self, obj: PytObjectPointer) -> pyt_ssize_t {
+        self.shadow.observe(obj);
+        My_REFCNT(obj)
+    }
+
+    // `ref_count_changed_by(obj, delta, ..)` and `refs_held_changed_by(held, ..)`
+    #[track_caller]
+    unsafe fn shadow_changed_by(&mut self, obj: PytObjectPointer, before: pyt_ssize_t, delta: pyt_ssize_t, held: pyt_ssize_t) {
+        self.shadow.changed_by(obj, before, delta);
+        self.refs_held += held;
+    }
+
+    // picks up the error indicator as CPython left it
+    unsafe fn shadow_error(&mut self) -> GpyErrorState {
+        self.error = shadow::current_error();
+        self.error
+    }
+
+    // `result` must be NULL with an error set, or a new reference described by `data` with the
+    // errors preserved; `fresh` if the specs promise a new object (reference count 1)
+    #[track_caller]
+    unsafe fn shadow_new_ref(&mut self, result: PytObjectPointer, data: ShadowData, fresh: bool) {
+        let old_error = self.error;
+        let error = self.shadow_error();
+        if result.is_null() {
+            assert!(error.is_set(), "shadow heap: NULL returned without an error set");
+            return;
+        }
+        assert!(error == old_error, "shadow heap: the error indicator changed although the call succeeded");
+        assert!(!fresh || My_REFCNT(result) == 1, "shadow heap: object {:#x} isn't new", result._private);
+        self.shadow.record(result, data);
+        self.refs_held += 1;
+    }
+
+    // the reference held to `item` was handed over: it is now in a container (`stored`, and
+    // `replaced` if that released the item there before) or it was released
+    #[track_caller]
+    unsafe fn shadow_steal(&mut self, item: PytObjectPointer, before: pyt_ssize_t, stored: bool, replaced: bool) {
+        if !stored {
+            self.shadow.changed_by(item, before, -1);
+        } else if replaced {
+            self.shadow.havoc();
+        }
+        self.shadow_error();
+        self.refs_held -= 1;
+    }
+
+    // `gpy_havoc`: Python code (or another thread) ran
+    fn shadow_havoc(&mut self) {
+        self.shadow.havoc();
+    }
+
+    // `gpy_attr_runs_code`: only the attributes of ints are known to be plain lookups
+    unsafe fn shadow_attr_access(&mut self, obj: PytObjectPointer) {
+        if MyLong_CheckExact(obj) == 0 {
+            self.shadow.havoc();
+        }
+    }
+
+    // what `pytobject_richcompare` promises, as far as the operands are known
+    #[track_caller]
+    unsafe fn shadow_compare_result(&mut self, op: PytCompareOp, obj0: PytObjectPointer, obj1: PytObjectPointer, result: PytObjectPointer) {
+        if MyLong_CheckExact(obj0) == 0 || MyLong_CheckExact(obj1) == 0 {
+            self.shadow.havoc();
+            self.shadow_new_ref(result, ShadowData::Other, false);
+            return;
         }
+        let data = match (self.shadow.get(obj0), self.shadow.get(obj1)) {
+            (Some(ShadowData::Long(Some(a))), Some(ShadowData::Long(Some(b)))) => ShadowData::Bool(op.holds(a, b)),
+            // True or False, whichever it is
+            _ => ShadowData::Bool(!result.is_null() && My_IsTrue(result) != 0),
+        };
+        self.shadow_new_ref(result, data, false);
+    }
+
+    // `gpy_number_result` as far as the operands are known
+    #[track_caller]
+    unsafe fn shadow_number_result(&mut self, op: GpyNumberOp, obj0: PytObjectPointer, obj1: PytObjectPointer, result: PytObjectPointer) {
+        let data = match (self.shadow.get(obj0), self.shadow.get(obj1)) {
+            (Some(ShadowData::Long(Some(a))), Some(ShadowData::Long(Some(b)))) => {
+                assert!(!op.divides_by_zero(a, b) || result.is_null() && shadow::current_error() == GpyErrorState::Set(PytExceptionType::ZeroDivisionError),
+                    "shadow heap: division by zero not reported");
+                match op.apply(a, b) {
+                    GpyLongData::Known(v) => ShadowData::Long(Some(v)),
+                    GpyLongData::Unknown if op == GpyNumberOp::Power && b < 0 => ShadowData::Other,
+                    GpyLongData::Unknown => ShadowData::Long(None),
+                }
+            }
+            (_, Some(ShadowData::Long(Some(b)))) if b >= 0 => ShadowData::Long(None),
+            _ if op != GpyNumberOp::Power => ShadowData::Long(None),
+            _ => ShadowData::Other,
+        };
+        self.shadow_new_ref(result, data, false);
     }
 }
 
@@ -52,8 +317,33 @@ obligation! {
     pub fn gpy_ref_held(amount: usize, obj: PytObjectPointer);
 }
 
+// permission to touch Python objects; `pyt_initialize` hands it out and it must be given up
+// (`pyt_eval_savethread`) before another thread can take it
+obligation! {
+    pub fn gpy_gil_held(amount: usize);
+}
+
+// a reference that `container` owns and that we are only allowed to look at; it must never be
+// decref'd and is only meaningful as long as `container` is alive and still holds `obj`
+obligation! {
+    pub fn gpy_ref_borrowed(amount: usize, container: PytObjectPointer, obj: PytObjectPointer);
+}
+
+// an exported buffer of `obj` that has to be given back with `pytbuffer_release`
+obligation! {
+    pub fn gpy_buffer_held(amount: usize, obj: PytObjectPointer);
+}
+
+// an exception taken out of the error indicator (`pyt_err_fetch`) that has to be handed back
+// (`pyt_err_restore`)
+obligation! {
+    pub fn gpy_error_fetched(amount: usize);
+}
+
+// marks a borrowed reference that is currently being used through `gpy_ref_held` (see
+// `pyt_borrow_begin`)
 obligation! {
-    fn gpy_initialized(amount: usize);
+    fn gpy_ref_lent(amount: usize, container: PytObjectPointer, obj: PytObjectPointer);
 }
 
 impl PytObjectPointer {
@@ -65,9 +355,30 @@ impl PytObjectPointer {
     // spec-only
     #[trusted]
     #[pure]
+    #[ensures(result.data.is_type() ==> result.typ === s.constants.py_type_type)]
+    #[ensures(result.data === GpyObjectData::PyNone ==> result.typ === s.constants.py_none_type)]
+    #[ensures(result.data.is_bool() ==> result.typ === s.constants.py_bool_type)]
+    #[ensures(result.data.is_long() ==> result.typ === s.constants.py_long_type)]
+    #[ensures(result.data === GpyObjectData::PyUnicode ==> result.typ === s.constants.py_unicode_type)]
+    #[ensures(result.data.is_list() ==> result.typ === s.constants.py_list_type)]
+    #[ensures(result.data.is_tuple() ==> result.typ === s.constants.py_tuple_type)]
+    #[ensures(result.data === GpyObjectData::PyDict ==> result.typ === s.constants.py_dict_type)]
+    #[ensures(result.data === GpyObjectData::PyModule ==> result.typ === s.constants.py_module_type)]
+    #[ensures(result.data.is_float() ==> result.typ === s.constants.py_float_type)]
+    #[ensures(result.data.is_bytes() ==> result.typ === s.constants.py_bytes_type)]
+    #[ensures(result.immortal ==> result.ref_count >= 1)]
     pub fn gpy_get(&self, s: &GpyGlobalState) -> GpyObject {
         unreachable!()
     }
+
+    // spec-only: the implementation describing the objects whose type is `self`
+    #[trusted]
+    #[pure]
+    #[ensures(*self === s.constants.py_long_type ==> result == GpyTypeImpl::Long)]
+    #[ensures(self.gpy_get(s).data.is_type() ==> self.gpy_get(s).data === GpyObjectData::PyType(result))]
+    fn gpy_type_impl(&self, s: &GpyGlobalState) -> GpyTypeImpl {
+        unreachable!()
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -75,16 +386,36 @@ struct GpyObject {
     ref_count: pyt_ssize_t,
     data: GpyObjectData,
     typ: PytObjectPointer,
+    lent: pyt_ssize_t, // number of active borrows this object takes part in (as the container or as the borrowed object)
+    immortal: bool, // since 3.12 some objects (None, True, False, small ints, ...) ignore incref/decref; which ones depends on the version
 }
 
 #[derive(Clone, Copy)]
 enum GpyObjectData {
     PyNone,
-    PyLong,
+    PyLong(GpyLongData),
     PyBool(bool),
     PyDict,
+    PyList(GpySequenceData),
+    PyTuple(GpySequenceData),
+    PyUnicode,
+    PyModule,
     PyNotImplemented,
-    //PyType(GpyType),
+    PyType(GpyTypeImpl),
+    PyFloat(f64),
+    PyBytes(pyt_ssize_t), // only the length is tracked
+}
+
+#[derive(Clone, Copy)]
+enum GpyLongData {
+    Known(libc::c_long),
+    Unknown, // either not known to the specs or not representable as a C long
+}
+
+#[derive(Clone, Copy)]
+struct GpySequenceData {
+    len: pyt_ssize_t,
+    items: Seq<PytObjectPointer>, // NULL for slots that haven't been filled in yet; every other item is a reference owned by the sequence
 }
 
 impl GpyObjectData {
@@ -92,271 +423,890 @@ impl GpyObjectData {
     fn is_bool(&self) -> bool {
         matches!(self, GpyObjectData::PyBool(_))
     }
+
+    // whether Python code can't change this data (as long as the object is alive)
+    #[pure]
+    fn is_immutable(&self) -> bool {
+        matches!(self,
+            GpyObjectData::PyNone | GpyObjectData::PyLong(_) | GpyObjectData::PyBool(_) | GpyObjectData::PyUnicode |
+            GpyObjectData::PyTuple(_) | GpyObjectData::PyNotImplemented | GpyObjectData::PyType(_) |
+            GpyObjectData::PyFloat(_) | GpyObjectData::PyBytes(_))
+    }
+
+    #[pure]
+    fn is_type(&self) -> bool {
+        matches!(self, GpyObjectData::PyType(_))
+    }
+
+    #[pure]
+    fn is_long(&self) -> bool {
+        matches!(self, GpyObjectData::PyLong(_))
+    }
+
+    #[pure]
+    fn is_known_long(&self) -> bool {
+        matches!(self, GpyObjectData::PyLong(GpyLongData::Known(_)))
+    }
+
+    #[pure]
+    #[requires(self.is_known_long())]
+    fn long_value(&self) -> libc::c_long {
+        match self {
+            GpyObjectData::PyLong(GpyLongData::Known(v)) => *v,
+            _ => unreachable!(),
+        }
+    }
+
+    #[pure]
+    fn is_list(&self) -> bool {
+        matches!(self, GpyObjectData::PyList(_))
+    }
+
+    #[pure]
+    fn is_tuple(&self) -> bool {
+        matches!(self, GpyObjectData::PyTuple(_))
+    }
+
+    #[pure]
+    fn is_float(&self) -> bool {
+        matches!(self, GpyObjectData::PyFloat(_))
+    }
+
+    #[pure]
+    #[requires(self.is_float())]
+    fn float_value(&self) -> f64 {
+        match self {
+            GpyObjectData::PyFloat(v) => *v,
+            _ => unreachable!(),
+        }
+    }
+
+    #[pure]
+    fn is_bytes(&self) -> bool {
+        matches!(self, GpyObjectData::PyBytes(_))
+    }
+
+    #[pure]
+    #[requires(self.is_bytes())]
+    fn bytes_len(&self) -> pyt_ssize_t {
+        match self {
+            GpyObjectData::PyBytes(len) => *len,
+            _ => unreachable!(),
+        }
+    }
+
+    #[pure]
+    fn is_sequence(&self) -> bool {
+        self.is_list() || self.is_tuple()
+    }
+
+    #[pure]
+    #[requires(self.is_sequence())]
+    fn seq_len(&self) -> pyt_ssize_t {
+        match self {
+            GpyObjectData::PyList(l) => l.len,
+            GpyObjectData::PyTuple(t) => t.len,
+            _ => unreachable!(),
+        }
+    }
+
+    #[pure]
+    #[requires(self.is_sequence())]
+    #[requires(0 <= index && index < self.seq_len())]
+    fn seq_item(&self, index: pyt_ssize_t) -> PytObjectPointer {
+        match self {
+            GpyObjectData::PyList(l) => l.items[Int::new(index as i64)],
+            GpyObjectData::PyTuple(t) => t.items[Int::new(index as i64)],
+            _ => unreachable!(),
+        }
+    }
 }
 
 trait GpyType {
+    // returns the result and the updated error state
     #[pure]
     fn getattr(obj: PytObjectPointer, name: PytObjectPointer, s: &GpyGlobalState) -> (PytObjectPointer, GpyErrorState);
+
+    // returns the updated object and the updated error state
+    #[pure]
+    fn setattr(obj: PytObjectPointer, name: PytObjectPointer, value: PytObjectPointer, s: &GpyGlobalState) -> (GpyObjectData, GpyErrorState);
+}
+
+// the `GpyType` implementations a type object can resolve to
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GpyTypeImpl {
+    Long,
+    Generic, // any type we don't know anything specific about
 }
 
 struct GpyLongType {}
 
+#[refine_trait_spec]
+impl GpyType for GpyLongType {
+    #[trusted]
+    #[pure]
+    #[ensures(!result.0.is_null() ==> !result.1.is_set())]
+    #[ensures(result.0.is_null() ==> result.1 === GpyErrorState::Set(PytExceptionType::AttributeError))]
+    fn getattr(obj: PytObjectPointer, name: PytObjectPointer, s: &GpyGlobalState) -> (PytObjectPointer, GpyErrorState) {
+        unreachable!()
+    }
 
-/*
-#[derive(Clone)]
-struct GpyType {
-    getattr: Option<fn(obj: PytObjectPointer, name: PytObjectPointer, s: &GpyGlobalState) -> (PytObjectPointer, GpyErrorState)>,
-    //setattr: Option<dyn GpySetattr>,
+    // ints don't have writable attributes
+    #[trusted]
+    #[pure]
+    #[ensures(result.0 === obj.gpy_get(s).data)]
+    #[ensures(result.1 === GpyErrorState::Set(PytExceptionType::AttributeError))]
+    fn setattr(obj: PytObjectPointer, name: PytObjectPointer, value: PytObjectPointer, s: &GpyGlobalState) -> (GpyObjectData, GpyErrorState) {
+        unreachable!()
+    }
 }
-*/
 
-/*
-trait GpyGetattr {
-    // returns the result and the updated error state
+struct GpyGenericType {}
+
+#[refine_trait_spec]
+impl GpyType for GpyGenericType {
+    #[trusted]
     #[pure]
-    fn getattr(obj: PytObjectPointer, name: PytObjectPointer, s: &GpyGlobalState) -> (PytObjectPointer, GpyErrorState);
-}
+    #[ensures(result.0.is_null() <==> result.1.is_set())]
+    fn getattr(obj: PytObjectPointer, name: PytObjectPointer, s: &GpyGlobalState) -> (PytObjectPointer, GpyErrorState) {
+        unreachable!()
+    }
 
-trait GpySetattr {
-    // returns the updated object and the updated error state
+    #[trusted]
     #[pure]
-    fn setattr(obj: PytObjectPointer, name: PytObjectPointer, s: &GpyGlobalState) -> (GpyObjectData, GpyErrorState);
+    #[ensures(result.1.is_set() ==> result.0 === obj.gpy_get(s).data)]
+    fn setattr(obj: PytObjectPointer, name: PytObjectPointer, value: PytObjectPointer, s: &GpyGlobalState) -> (GpyObjectData, GpyErrorState) {
+        unreachable!()
+    }
+}
+
+#[pure]
+fn gpy_getattr(obj: PytObjectPointer, name: PytObjectPointer, s: &GpyGlobalState) -> (PytObjectPointer, GpyErrorState) {
+    match obj.gpy_get(s).typ.gpy_type_impl(s) {
+        GpyTypeImpl::Long => GpyLongType::getattr(obj, name, s),
+        GpyTypeImpl::Generic => GpyGenericType::getattr(obj, name, s),
+    }
+}
+
+#[pure]
+fn gpy_setattr(obj: PytObjectPointer, name: PytObjectPointer, value: PytObjectPointer, s: &GpyGlobalState) -> (GpyObjectData, GpyErrorState) {
+    match obj.gpy_get(s).typ.gpy_type_impl(s) {
+        GpyTypeImpl::Long => GpyLongType::setattr(obj, name, value, s),
+        GpyTypeImpl::Generic => GpyGenericType::setattr(obj, name, value, s),
+    }
+}
+
+// whether getting or setting an attribute of `obj` may run Python code (`__getattr__`, properties,
+// ...); the attributes of types we know nothing about may do anything
+#[pure]
+fn gpy_attr_runs_code(obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
+    match obj.gpy_get(s).typ.gpy_type_impl(s) {
+        GpyTypeImpl::Long => false,
+        GpyTypeImpl::Generic => true,
+    }
 }
-*/
 
 extern "C" {
     fn Py_Initialize();
     fn Py_Finalize();
     fn Py_IncRef(obj: PytObjectPointer);
     fn Py_DecRef(obj: PytObjectPointer);
-    fn Py_REFCNT(obj: PytObjectPointer) -> pyt_ssize_t;
+    fn My_REFCNT(obj: PytObjectPointer) -> pyt_ssize_t;
+    fn My_IsImmortal(obj: PytObjectPointer) -> libc::c_int;
+
+    fn PyEval_SaveThread() -> *mut libc::c_void;
+    fn PyEval_RestoreThread(tstate: *mut libc::c_void);
+    fn PyGILState_Ensure() -> libc::c_int;
+    fn PyGILState_Release(state: libc::c_int);
+    fn PyGILState_Check() -> libc::c_int;
 
     fn PyLong_AsLong(obj: PytObjectPointer) -> libc::c_long;
-    fn PyLong_FromLong(v: libc::c_long) -> PytObjectPointer;
-    fn MyLong_CheckExact(obj: PytObjectPointer) -> libc::c_int;
 
-    fn PyDict_New() -> PytObjectPointer;
-    fn MyDict_CheckExact(obj: PytObjectPointer) -> libc::c_int;
+    fn PyList_New(len: pyt_ssize_t) -> PytObjectPointer;
+    fn PyList_Size(list: PytObjectPointer) -> pyt_ssize_t;
+    fn PyList_GetItem(list: PytObjectPointer, index: pyt_ssize_t) -> PytObjectPointer;
+
+    fn PyTuple_New(len: pyt_ssize_t) -> PytObjectPointer;
+    fn PyTuple_Size(tuple: PytObjectPointer) -> pyt_ssize_t;
+    fn PyTuple_GetItem(tuple: PytObjectPointer, index: pyt_ssize_t) -> PytObjectPointer;
 
-    fn MyNone_GetNoIncRef() -> PytObjectPointer;
-    fn MyBool_GetTrueNoIncRef() -> PytObjectPointer;
-    fn MyBool_GetFalseNoIncRef() -> PytObjectPointer;
+    fn PyFloat_AsDouble(obj: PytObjectPointer) -> f64;
 
-    fn MyBool_Check(obj: PytObjectPointer) -> libc::c_int;
+    fn PyBytes_FromStringAndSize(v: *const libc::c_char, len: pyt_ssize_t) -> PytObjectPointer;
+    fn PyBytes_AsString(obj: PytObjectPointer) -> *mut libc::c_char;
+    fn PyBytes_Size(obj: PytObjectPointer) -> pyt_ssize_t;
+
+    fn PyImport_ImportModule(name: *const libc::c_char) -> PytObjectPointer;
+    fn PyRun_SimpleString(command: *const libc::c_char) -> libc::c_int;
+    fn PyModule_AddObjectRef(module: PytObjectPointer, name: *const libc::c_char, value: PytObjectPointer) -> libc::c_int;
 
     fn My_IsNone(obj: PytObjectPointer) -> libc::c_int;
     fn My_IsTrue(obj: PytObjectPointer) -> libc::c_int;
     fn My_IsFalse(obj: PytObjectPointer) -> libc::c_int;
     fn My_Is(obj0: PytObjectPointer, obj1: PytObjectPointer) -> libc::c_int;
 
+    fn My_Type(obj: PytObjectPointer) -> PytObjectPointer;
+    fn My_IsType(obj: PytObjectPointer, typ: PytObjectPointer) -> libc::c_int;
+
+    fn PyObject_GetAttr(obj: PytObjectPointer, name: PytObjectPointer) -> PytObjectPointer;
+    fn PyObject_SetAttr(obj: PytObjectPointer, name: PytObjectPointer, value: PytObjectPointer) -> libc::c_int;
     fn PyObject_HasAttr(obj: PytObjectPointer, name: PytObjectPointer) -> libc::c_int;
+    fn PyObject_CallNoArgs(callable: PytObjectPointer) -> PytObjectPointer;
+    fn PyObject_CallOneArg(callable: PytObjectPointer, arg: PytObjectPointer) -> PytObjectPointer;
+    fn PyObject_CallObject(callable: PytObjectPointer, args: PytObjectPointer) -> PytObjectPointer;
+    fn PyObject_GetAttrString(obj: PytObjectPointer, name: *const libc::c_char) -> PytObjectPointer;
+    fn PyObject_RichCompare(obj0: PytObjectPointer, obj1: PytObjectPointer, op: libc::c_int) -> PytObjectPointer;
+    fn PyObject_IsTrue(obj: PytObjectPointer) -> libc::c_int;
+    fn PyObject_GetIter(obj: PytObjectPointer) -> PytObjectPointer;
+    fn PyIter_Next(iter: PytObjectPointer) -> PytObjectPointer;
+
+    fn PyNumber_Add(obj0: PytObjectPointer, obj1: PytObjectPointer) -> PytObjectPointer;
+    fn PyNumber_Subtract(obj0: PytObjectPointer, obj1: PytObjectPointer) -> PytObjectPointer;
+    fn PyNumber_Multiply(obj0: PytObjectPointer, obj1: PytObjectPointer) -> PytObjectPointer;
+    fn PyNumber_FloorDivide(obj0: PytObjectPointer, obj1: PytObjectPointer) -> PytObjectPointer;
+    fn PyNumber_Power(obj0: PytObjectPointer, obj1: PytObjectPointer, obj2: PytObjectPointer) -> PytObjectPointer;
+
+    fn PyErr_Occurred() -> PytObjectPointer;
+    fn PyErr_Clear();
+    fn PyErr_SetString(exc: PytObjectPointer, message: *const libc::c_char);
+    fn PyErr_Fetch(ptype: *mut PytObjectPointer, pvalue: *mut PytObjectPointer, ptraceback: *mut PytObjectPointer);
+    fn PyErr_Restore(typ: PytObjectPointer, value: PytObjectPointer, traceback: PytObjectPointer);
+    fn PyErr_ExceptionMatches(exc: PytObjectPointer) -> libc::c_int;
+
+    fn PyObject_GetBuffer(obj: PytObjectPointer, view: *mut PytBuffer, flags: libc::c_int) -> libc::c_int;
+    fn PyBuffer_Release(view: *mut PytBuffer);
+
+    static PyExc_OverflowError: PytObjectPointer;
+    static PyExc_ZeroDivisionError: PytObjectPointer;
+    static PyExc_MemoryError: PytObjectPointer;
+    static PyExc_TypeError: PytObjectPointer;
+    static PyExc_ValueError: PytObjectPointer;
+    static PyExc_IndexError: PytObjectPointer;
+    static PyExc_KeyError: PytObjectPointer;
+    static PyExc_AttributeError: PytObjectPointer;
+    static PyExc_SystemError: PytObjectPointer;
+    static PyExc_BufferError: PytObjectPointer;
 }
 
+// the checks, getters and constructors that follow a common pattern (see src/api.table)
+include!(concat!(env!("OUT_DIR"), "/api.rs"));
+
 predicate! {
     fn all_other_preserved(changed: PytObjectPointer, s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
         forall(|q: PytObjectPointer| (q !== changed ==> q.gpy_get(s) === q.gpy_get(s0)))
     }
 }
 
+predicate! {
+    fn all_other_two_preserved(changed0: PytObjectPointer, changed1: PytObjectPointer, s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
+        forall(|q: PytObjectPointer| (q !== changed0 && q !== changed1 ==> q.gpy_get(s) === q.gpy_get(s0)))
+    }
+}
+
 predicate! {
     fn all_objects_preserved(s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
         forall(|q: PytObjectPointer| q.gpy_get(s) === q.gpy_get(s0))
     }
 }
 
+// what running arbitrary Python code may do: mutable objects can change in any way and objects can
+// be created, referenced or released, but immutable data, types and borrows stay as they were
+predicate! {
+    fn gpy_havoc(s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
+        forall(|q: PytObjectPointer| (q.gpy_get(s0).ref_count >= 1 && q.gpy_get(s).ref_count >= 1 ==> q.gpy_get(s).typ === q.gpy_get(s0).typ))
+        && forall(|q: PytObjectPointer| (q.gpy_get(s0).ref_count >= 1 && q.gpy_get(s).ref_count >= 1 && q.gpy_get(s0).data.is_immutable() ==> q.gpy_get(s).data === q.gpy_get(s0).data))
+        && forall(|q: PytObjectPointer| q.gpy_get(s).lent == q.gpy_get(s0).lent)
+    }
+}
+
 predicate! {
     fn errors_preserved(s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
-        s0.error == s.error
+        s0.error === s.error
     }
 }
 
 predicate! {
-    fn constants_preserved(s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
-        s0.constants === s.constants
+    fn refs_held_changed_by(delta: pyt_ssize_t, s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
+        s.refs_held == s0.refs_held + delta
     }
 }
 
-#[trusted]
-#[ensures(gpy_initialized(1))]
-pub unsafe fn pyt_initialize() {
-    Py_Initialize();
+predicate! {
+    fn refs_held_preserved(s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
+        s.refs_held == s0.refs_held
+    }
+}
+
+// for functions that must give back (decref or hand over) every reference they acquire
+predicate! {
+    pub fn gpy_refs_balanced(s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
+        s.refs_held == s0.refs_held
+    }
+}
+
+// which objects are immortal depends on the Python version, so specs that touch a reference count
+// must hold either way
+predicate! {
+    fn ref_count_changed_by(obj: PytObjectPointer, delta: pyt_ssize_t, s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
+        obj.gpy_get(s).immortal == obj.gpy_get(s0).immortal
+        && (obj.gpy_get(s0).immortal ==> obj.gpy_get(s).ref_count == obj.gpy_get(s0).ref_count)
+        && (!obj.gpy_get(s0).immortal ==> obj.gpy_get(s).ref_count == obj.gpy_get(s0).ref_count + delta)
+    }
+}
+
+predicate! {
+    fn constants_preserved(s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
+        s0.constants === s.constants
+    }
 }
 
+// every reference handed out must have been given back by now, otherwise it has leaked; the
+// states of other threads (`pyt_gilstate_ensure`) can't finalize
 #[trusted]
-#[requires(gpy_initialized(1))]
-pub unsafe fn pyt_finalize() {
-    Py_Finalize();
+#[requires(gpy_gil_held(1))]
+#[requires(s.refs_held == 0)]
+#[requires(s.owns_interpreter())]
+pub fn pyt_finalize(s: GpyGlobalState) -> PytInterpreter {
+    #[cfg(feature = "shadow-heap")]
+    assert!(s.refs_held == 0, "shadow heap: {} references were never given back", s.refs_held);
+    let interpreter = s.interpreter.expect("only the state `pyt_initialize` handed out can finalize");
+    unsafe {
+        Py_Finalize();
+    }
+    INTERPRETER.store(0, Ordering::SeqCst);
+    interpreter
 }
 
 #[trusted]
-#[requires(gpy_initialized(1))]
+#[requires(gpy_gil_held(1))]
 #[requires(gpy_ref_held(1, obj))]
-#[ensures(gpy_initialized(1))]
+#[ensures(gpy_gil_held(1))]
 #[ensures(gpy_ref_held(2, obj))]
-#[ensures(obj.gpy_get(old(s)).ref_count + 1 == obj.gpy_get(s).ref_count)]
+#[ensures(ref_count_changed_by(obj, 1, old(s), s))]
+#[ensures(obj.gpy_get(old(s)).lent == obj.gpy_get(s).lent)]
 #[ensures(all_other_preserved(obj, old(s), s))]
 #[ensures(errors_preserved(old(s), s))]
 #[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_changed_by(1, old(s), s))]
 pub unsafe fn pyt_incref(obj: PytObjectPointer, s: &mut GpyGlobalState) {
-    Py_IncRef(obj)
+    #[cfg(feature = "shadow-heap")]
+    let before = s.shadow_use(obj);
+    Py_IncRef(obj);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_changed_by(obj, before, 1, 1);
 }
 
 #[trusted]
-#[requires(gpy_initialized(1))]
+#[requires(gpy_gil_held(1))]
 #[requires(gpy_ref_held(1, obj))]
-#[ensures(gpy_initialized(1))]
-#[ensures(obj.gpy_get(old(s)).ref_count - 1 == obj.gpy_get(s).ref_count)]
+#[requires(obj.gpy_get(s).lent == 0)]
+#[ensures(gpy_gil_held(1))]
+#[ensures(ref_count_changed_by(obj, -1, old(s), s))]
+#[ensures(obj.gpy_get(old(s)).lent == obj.gpy_get(s).lent)]
 #[ensures(all_other_preserved(obj, old(s), s))]
 #[ensures(errors_preserved(old(s), s))]
 #[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_changed_by(-1, old(s), s))]
 pub unsafe fn pyt_decref(obj: PytObjectPointer, s: &mut GpyGlobalState) {
-    Py_DecRef(obj)
+    #[cfg(feature = "shadow-heap")]
+    let before = s.shadow_use(obj);
+    Py_DecRef(obj);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_changed_by(obj, before, -1, -1);
 }
 
+// `Py_XINCREF`: like `pyt_incref`, but does nothing for NULL
 #[trusted]
-#[pure]
-#[requires(gpy_initialized(1))]
-#[requires(gpy_ref_held(1, obj))]
-#[ensures(result == obj.gpy_get(s).ref_count)]
-pub unsafe fn pyt_refcnt(obj: PytObjectPointer, s: &GpyGlobalState) -> pyt_ssize_t {
-    Py_REFCNT(obj)
+#[requires(gpy_gil_held(1))]
+#[requires(!obj.is_null() ==> gpy_ref_held(1, obj))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(!obj.is_null() ==> gpy_ref_held(2, obj))]
+#[ensures(!obj.is_null() ==> ref_count_changed_by(obj, 1, old(s), s) && obj.gpy_get(old(s)).lent == obj.gpy_get(s).lent)]
+#[ensures(!obj.is_null() ==> all_other_preserved(obj, old(s), s) && refs_held_changed_by(1, old(s), s))]
+#[ensures(obj.is_null() ==> all_objects_preserved(old(s), s) && refs_held_preserved(old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+pub unsafe fn pyt_xincref(obj: PytObjectPointer, s: &mut GpyGlobalState) {
+    #[cfg(feature = "shadow-heap")]
+    let before = if obj.is_null() { 0 } else { s.shadow_use(obj) };
+    Py_IncRef(obj);
+    #[cfg(feature = "shadow-heap")]
+    if !obj.is_null() {
+        s.shadow_changed_by(obj, before, 1, 1);
+    }
 }
 
+// `Py_XDECREF`: like `pyt_decref`, but does nothing for NULL
 #[trusted]
-#[requires(gpy_initialized(1))]
-#[requires(gpy_ref_held(1, obj))]
-#[ensures(gpy_initialized(1))]
-#[ensures(gpy_ref_held(1, obj))]
-#[ensures(all_objects_preserved(old(s), s))]
+#[requires(gpy_gil_held(1))]
+#[requires(!obj.is_null() ==> gpy_ref_held(1, obj))]
+#[requires(!obj.is_null() ==> obj.gpy_get(s).lent == 0)]
+#[ensures(gpy_gil_held(1))]
+#[ensures(!obj.is_null() ==> ref_count_changed_by(obj, -1, old(s), s) && obj.gpy_get(old(s)).lent == obj.gpy_get(s).lent)]
+#[ensures(!obj.is_null() ==> all_other_preserved(obj, old(s), s) && refs_held_changed_by(-1, old(s), s))]
+#[ensures(obj.is_null() ==> all_objects_preserved(old(s), s) && refs_held_preserved(old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
 #[ensures(constants_preserved(old(s), s))]
-pub unsafe fn pytlong_aslong(obj: PytObjectPointer, s: &mut GpyGlobalState) -> libc::c_long {
-    PyLong_AsLong(obj)
+pub unsafe fn pyt_xdecref(obj: PytObjectPointer, s: &mut GpyGlobalState) {
+    #[cfg(feature = "shadow-heap")]
+    let before = if obj.is_null() { 0 } else { s.shadow_use(obj) };
+    Py_DecRef(obj);
+    #[cfg(feature = "shadow-heap")]
+    if !obj.is_null() {
+        s.shadow_changed_by(obj, before, -1, -1);
+    }
 }
 
+// `Py_NewRef`: increfs `obj` and returns it, for handing a reference over in a single expression
 #[trusted]
-#[requires(gpy_initialized(1))]
-#[ensures(gpy_initialized(1))]
-#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & all_other_preserved(result, old(s), s) & (result.gpy_get(s).ref_count == result.gpy_get(old(s)).ref_count + 1) & (result.gpy_get(s).data === GpyObjectData::PyLong))]
-#[ensures(result.is_null() ==> all_objects_preserved(old(s), s))]
-#[ensures(result.is_null() ==> s.error)]
-#[ensures(!result.is_null() ==> errors_preserved(old(s), s))]
+#[requires(gpy_gil_held(1))]
+#[requires(!obj.is_null())]
+#[requires(gpy_ref_held(1, obj))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(result === obj)]
+#[ensures(gpy_ref_held(2, obj))]
+#[ensures(ref_count_changed_by(obj, 1, old(s), s))]
+#[ensures(obj.gpy_get(old(s)).lent == obj.gpy_get(s).lent)]
+#[ensures(all_other_preserved(obj, old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
 #[ensures(constants_preserved(old(s), s))]
-pub unsafe fn pytlong_fromlong(v: libc::c_long, s: &mut GpyGlobalState) -> PytObjectPointer {
-    PyLong_FromLong(v)
+#[ensures(refs_held_changed_by(1, old(s), s))]
+pub unsafe fn pyt_newref(obj: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    #[cfg(feature = "shadow-heap")]
+    let before = s.shadow_use(obj);
+    Py_IncRef(obj);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_changed_by(obj, before, 1, 1);
+    obj
 }
 
-
+// `Py_XNewRef`: like `pyt_newref`, but passes NULL through
 #[trusted]
-#[pure]
-#[requires(gpy_initialized(1))]
-#[requires(gpy_ref_held(1, obj))]
-#[ensures(result <==> obj.gpy_get(s).data === GpyObjectData::PyLong)]
-pub unsafe fn pylong_checkexact(obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
-    MyLong_CheckExact(obj) != 0
+#[requires(gpy_gil_held(1))]
+#[requires(!obj.is_null() ==> gpy_ref_held(1, obj))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(result === obj)]
+#[ensures(!obj.is_null() ==> gpy_ref_held(2, obj))]
+#[ensures(!obj.is_null() ==> ref_count_changed_by(obj, 1, old(s), s) && obj.gpy_get(old(s)).lent == obj.gpy_get(s).lent)]
+#[ensures(!obj.is_null() ==> all_other_preserved(obj, old(s), s) && refs_held_changed_by(1, old(s), s))]
+#[ensures(obj.is_null() ==> all_objects_preserved(old(s), s) && refs_held_preserved(old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+pub unsafe fn pyt_xnewref(obj: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    #[cfg(feature = "shadow-heap")]
+    let before = if obj.is_null() { 0 } else { s.shadow_use(obj) };
+    Py_IncRef(obj);
+    #[cfg(feature = "shadow-heap")]
+    if !obj.is_null() {
+        s.shadow_changed_by(obj, before, 1, 1);
+    }
+    obj
 }
 
+// `Py_CLEAR`: sets `*obj` to NULL before giving up the reference it held (if any), so `*obj` never
+// points to a released object, even while a destructor runs
 #[trusted]
-#[requires(gpy_initialized(1))]
-#[ensures(gpy_initialized(1))]
-#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(old(s)).ref_count == 0) & (result.gpy_get(s).data === GpyObjectData::PyDict) & (result.gpy_get(s).ref_count == 1))]
-#[ensures(result.is_null() ==> all_objects_preserved(old(s), s))]
-#[ensures(result.is_null() ==> s.error)]
-#[ensures(!result.is_null() ==> errors_preserved(old(s), s))]
+#[requires(gpy_gil_held(1))]
+#[requires(!obj.is_null() ==> gpy_ref_held(1, *obj))]
+#[requires(!obj.is_null() ==> obj.gpy_get(s).lent == 0)]
+#[ensures(gpy_gil_held(1))]
+#[ensures(obj.is_null())]
+#[ensures(!old(*obj).is_null() ==> ref_count_changed_by(old(*obj), -1, old(s), s) && old(*obj).gpy_get(old(s)).lent == old(*obj).gpy_get(s).lent)]
+#[ensures(!old(*obj).is_null() ==> all_other_preserved(old(*obj), old(s), s) && refs_held_changed_by(-1, old(s), s))]
+#[ensures(old(*obj).is_null() ==> all_objects_preserved(old(s), s) && refs_held_preserved(old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
 #[ensures(constants_preserved(old(s), s))]
-pub unsafe fn pytdict_new(s: &mut GpyGlobalState) -> PytObjectPointer {
-    PyDict_New()
+pub unsafe fn pyt_clear(obj: &mut PytObjectPointer, s: &mut GpyGlobalState) {
+    let tmp = *obj;
+    #[cfg(feature = "shadow-heap")]
+    let before = if tmp.is_null() { 0 } else { s.shadow_use(tmp) };
+    *obj = PytObjectPointer { _private: 0 };
+    Py_DecRef(tmp);
+    #[cfg(feature = "shadow-heap")]
+    if !tmp.is_null() {
+        s.shadow_changed_by(tmp, before, -1, -1);
+    }
 }
 
 #[trusted]
 #[pure]
-#[requires(gpy_initialized(1))]
 #[requires(gpy_ref_held(1, obj))]
-#[ensures(result <==> obj.gpy_get(s).data === GpyObjectData::PyDict)]
-pub unsafe fn pytdict_checkexact(obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
-    MyDict_CheckExact(obj) != 0
+#[ensures(result == obj.gpy_get(s).ref_count)]
+pub unsafe fn pyt_refcnt(obj: PytObjectPointer, s: &GpyGlobalState) -> pyt_ssize_t {
+    My_REFCNT(obj)
 }
 
-#[trusted]
-#[pure]
-#[requires(gpy_initialized(1))]
-#[ensures(result === s.constants.py_none)]
-pub unsafe fn pytnone_getnoincref(s: &GpyGlobalState) -> PytObjectPointer {
-    MyNone_GetNoIncRef()
+// the thread state saved by `pyt_eval_savethread`; it must be restored on the same thread
+pub struct PytThreadState {
+    ptr: *mut libc::c_void,
 }
 
-#[trusted]
-#[requires(gpy_initialized(1))]
-#[ensures(gpy_initialized(1))]
-#[ensures(gpy_ref_held(1, result))]
-#[ensures(result === s.constants.py_none)]
-#[ensures(constants_preserved(old(s), s))]
-#[ensures(all_other_preserved(s.constants.py_none, old(s), s))]
-#[ensures(s.constants.py_none.gpy_get(s).data === s.constants.py_none.gpy_get(old(s)).data)]
-#[ensures(s.constants.py_none.gpy_get(s).ref_count == s.constants.py_none.gpy_get(old(s)).ref_count + 1)]
-pub unsafe fn pytnone_get(s: &mut GpyGlobalState) -> PytObjectPointer {
-    let obj = MyNone_GetNoIncRef();
-    Py_IncRef(obj);
-    obj
+// what `pyt_gilstate_ensure` returns; it must be passed back to `pyt_gilstate_release` on the same thread
+pub struct PytGilState {
+    state: libc::c_int,
+    _thread: std::marker::PhantomData<*const ()>, // not `Send`
 }
 
+// releases the GIL; nothing Python may be touched until `pyt_eval_restorethread`
 #[trusted]
-#[pure]
-#[requires(gpy_initialized(1))]
-#[ensures(result === s.constants.py_true)]
-pub unsafe fn pytbool_gettruenoincref(s: &GpyGlobalState) -> PytObjectPointer {
-    MyBool_GetTrueNoIncRef()
+#[requires(gpy_gil_held(1))]
+#[ensures(all_objects_preserved(old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pyt_eval_savethread(s: &mut GpyGlobalState) -> PytThreadState {
+    PytThreadState { ptr: PyEval_SaveThread() }
 }
 
+// other threads may have run Python code in the meantime, so any mutable object may have changed
 #[trusted]
-#[requires(gpy_initialized(1))]
-#[ensures(gpy_initialized(1))]
-#[ensures(gpy_ref_held(1, result))]
-#[ensures(result === s.constants.py_true)]
+#[requires(gpy_no_borrows_active(s))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_havoc(old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
 #[ensures(constants_preserved(old(s), s))]
-#[ensures(all_other_preserved(s.constants.py_true, old(s), s))]
-#[ensures(s.constants.py_true.gpy_get(s).data === s.constants.py_true.gpy_get(old(s)).data)]
-#[ensures(s.constants.py_true.gpy_get(s).ref_count == s.constants.py_true.gpy_get(old(s)).ref_count + 1)]
-pub unsafe fn pytbool_gettrue(s: &GpyGlobalState) -> PytObjectPointer {
-    let obj = MyBool_GetTrueNoIncRef();
-    Py_IncRef(obj);
-    obj
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pyt_eval_restorethread(tstate: PytThreadState, s: &mut GpyGlobalState) {
+    PyEval_RestoreThread(tstate.ptr);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_havoc();
 }
 
+// for threads not created by Python: takes the GIL and hands out a state for this thread, which
+// lives until the matching `pyt_gilstate_release`. A thread holding the GIL already has a state
+// (`pyt_initialize`, a callback) and must use that one, so this checks that it doesn't.
 #[trusted]
-#[pure]
-#[requires(gpy_initialized(1))]
-#[ensures(result === s.constants.py_false)]
-pub unsafe fn pytbool_getfalsenoincref(s: &GpyGlobalState) -> PytObjectPointer {
-    MyBool_GetFalseNoIncRef()
+#[ensures(gpy_state_started(&result.1))]
+#[ensures(gpy_gil_held(1))]
+pub fn pyt_gilstate_ensure() -> (PytGilState, GpyGlobalState) {
+    assert!(INTERPRETER.load(Ordering::SeqCst) != 0, "the interpreter isn't running");
+    assert!(unsafe { PyGILState_Check() } == 0, "this thread holds the GIL already");
+    let state = unsafe { PyGILState_Ensure() };
+    (PytGilState { state, _thread: std::marker::PhantomData }, gpy_new_state())
 }
 
+// gives the GIL back; the thread's state goes with it, so it must not hold any references
 #[trusted]
-#[requires(gpy_initialized(1))]
-#[ensures(gpy_initialized(1))]
-#[ensures(gpy_ref_held(1, result))]
-#[ensures(result === s.constants.py_false)]
+#[requires(gpy_gil_held(1))]
+#[requires(s.refs_held == 0)]
+#[requires(gpy_no_borrows_active(&s))]
+pub fn pyt_gilstate_release(gstate: PytGilState, s: GpyGlobalState) {
+    unsafe { PyGILState_Release(gstate.state) }
+}
+
+// runs `f` with the GIL released; `f` cannot reach `s`, so it has no way of touching Python objects
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_no_borrows_active(s))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_havoc(old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
 #[ensures(constants_preserved(old(s), s))]
-#[ensures(all_other_preserved(s.constants.py_false, old(s), s))]
-#[ensures(s.constants.py_false.gpy_get(s).data === s.constants.py_false.gpy_get(old(s)).data)]
-#[ensures(s.constants.py_false.gpy_get(s).ref_count == s.constants.py_false.gpy_get(old(s)).ref_count + 1)]
-pub unsafe fn pytbool_getfalse(s: &GpyGlobalState) -> PytObjectPointer {
-    let obj = MyBool_GetFalseNoIncRef();
-    Py_IncRef(obj);
-    obj
+#[ensures(refs_held_preserved(old(s), s))]
+pub fn pyt_allow_threads<T, F: FnOnce() -> T>(s: &mut GpyGlobalState, f: F) -> T {
+    let tstate = unsafe { PyEval_SaveThread() };
+    let result = f();
+    unsafe {
+        PyEval_RestoreThread(tstate);
+    }
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_havoc();
+    result
+}
+
+// -1 is also a valid result, so the only way to tell an overflow apart is to look at the error
+// indicator (which is why it must be clear beforehand)
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj))]
+#[requires(obj.gpy_get(s).data.is_long())]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj))]
+#[ensures(all_objects_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(obj.gpy_get(s).data.is_known_long() ==> result == obj.gpy_get(s).data.long_value() && !s.error.is_set())]
+#[ensures(s.error.is_set() ==> (result == -1) & (s.error === GpyErrorState::Set(PytExceptionType::OverflowError)))]
+#[ensures(result != -1 ==> !s.error.is_set())]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pytlong_aslong(obj: PytObjectPointer, s: &mut GpyGlobalState) -> libc::c_long {
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_use(obj);
+    let result = PyLong_AsLong(obj);
+    #[cfg(feature = "shadow-heap")]
+    {
+        let error = s.shadow_error();
+        assert!(!error.is_set() || result == -1 && error == GpyErrorState::Set(PytExceptionType::OverflowError),
+            "shadow heap: unexpected error from PyLong_AsLong");
+        if let Some(ShadowData::Long(Some(v))) = s.shadow.get(obj) {
+            assert!(result == v && !error.is_set(), "shadow heap: object {:#x} is {} instead of {}", obj._private, result, v);
+        }
+    }
+    result
+}
+
+predicate! {
+    pub fn gpy_no_borrows_active(s: &GpyGlobalState) -> bool {
+        forall(|q: PytObjectPointer| q.gpy_get(s).lent == 0)
+    }
+}
+
+// whether `container` holds a reference to `obj`
+predicate! {
+    fn gpy_contains(container: PytObjectPointer, obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
+        container.gpy_get(s).typ === obj ||
+        container.gpy_get(s).data.is_sequence() &&
+        exists(|i: pyt_ssize_t| (0 <= i && i < container.gpy_get(s).data.seq_len() && container.gpy_get(s).data.seq_item(i) === obj))
+    }
+}
+
+predicate! {
+    fn gpy_borrow_valid(container: PytObjectPointer, obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
+        !obj.is_null() && container.gpy_get(s).ref_count >= 1 && gpy_contains(container, obj, s)
+    }
+}
+
+// ghost operation, does nothing at runtime: lets a borrowed reference be used by the functions
+// that take `gpy_ref_held`; neither `obj` nor `container` can be decref'd or mutated until the
+// matching `pyt_borrow_end`
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_borrowed(1, container, obj))]
+#[requires(gpy_borrow_valid(container, obj, s))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj))]
+#[ensures(gpy_ref_lent(1, container, obj))]
+#[ensures(obj.gpy_get(s).lent == obj.gpy_get(old(s)).lent + 1)]
+#[ensures(container.gpy_get(s).lent == container.gpy_get(old(s)).lent + 1)]
+#[ensures(obj.gpy_get(s).ref_count == obj.gpy_get(old(s)).ref_count && obj.gpy_get(s).data === obj.gpy_get(old(s)).data)]
+#[ensures(container.gpy_get(s).ref_count == container.gpy_get(old(s)).ref_count && container.gpy_get(s).data === container.gpy_get(old(s)).data)]
+#[ensures(all_other_two_preserved(container, obj, old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_changed_by(1, old(s), s))]
+pub fn pyt_borrow_begin(container: PytObjectPointer, obj: PytObjectPointer, s: &mut GpyGlobalState) {
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.refs_held += 1;
+    }
+}
+
+// ghost operation, does nothing at runtime: gives back what `pyt_borrow_begin` handed out
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj))]
+#[requires(gpy_ref_lent(1, container, obj))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_borrowed(1, container, obj))]
+#[ensures(obj.gpy_get(s).lent == obj.gpy_get(old(s)).lent - 1)]
+#[ensures(container.gpy_get(s).lent == container.gpy_get(old(s)).lent - 1)]
+#[ensures(obj.gpy_get(s).ref_count == obj.gpy_get(old(s)).ref_count && obj.gpy_get(s).data === obj.gpy_get(old(s)).data)]
+#[ensures(container.gpy_get(s).ref_count == container.gpy_get(old(s)).ref_count && container.gpy_get(s).data === container.gpy_get(old(s)).data)]
+#[ensures(all_other_two_preserved(container, obj, old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_changed_by(-1, old(s), s))]
+pub fn pyt_borrow_end(container: PytObjectPointer, obj: PytObjectPointer, s: &mut GpyGlobalState) {
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.refs_held -= 1;
+    }
+}
+
+predicate! {
+    fn seq_items_preserved_except(seq: PytObjectPointer, index: pyt_ssize_t, s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
+        seq.gpy_get(s).data.is_list() == seq.gpy_get(s0).data.is_list() &&
+        seq.gpy_get(s).data.is_tuple() == seq.gpy_get(s0).data.is_tuple() &&
+        seq.gpy_get(s).data.seq_len() == seq.gpy_get(s0).data.seq_len() &&
+        forall(|i: pyt_ssize_t| (0 <= i && i < seq.gpy_get(s).data.seq_len() && i != index ==> seq.gpy_get(s).data.seq_item(i) === seq.gpy_get(s0).data.seq_item(i)))
+    }
+}
+
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(len >= 0)]
+#[ensures(gpy_gil_held(1))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(old(s)).ref_count == 0) & (result.gpy_get(s).ref_count == 1) & all_other_preserved(result, old(s), s) & !result.gpy_get(s).immortal)]
+#[ensures(!result.is_null() ==> result.gpy_get(s).data.is_list() && result.gpy_get(s).data.seq_len() == len && result.gpy_get(s).lent == 0)]
+#[ensures(!result.is_null() ==> forall(|i: pyt_ssize_t| (0 <= i && i < len ==> result.gpy_get(s).data.seq_item(i).is_null())))]
+#[ensures(result.is_null() ==> all_objects_preserved(old(s), s))]
+#[ensures(result.is_null() ==> (s.error === GpyErrorState::Set(PytExceptionType::MemoryError)))]
+#[ensures(!result.is_null() ==> errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytlist_new(len: pyt_ssize_t, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyList_New(len);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_new_ref(result, ShadowData::List, true);
+    result
+}
+
+#[trusted]
+#[pure]
+#[requires(gpy_ref_held(1, list))]
+#[requires(list.gpy_get(s).data.is_list())]
+#[ensures(result == list.gpy_get(s).data.seq_len())]
+pub unsafe fn pytlist_size(list: PytObjectPointer, s: &GpyGlobalState) -> pyt_ssize_t {
+    PyList_Size(list)
+}
+
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, list))]
+#[requires(list.gpy_get(s).data.is_list())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, list))]
+#[ensures(all_objects_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(0 <= index && index < list.gpy_get(s).data.seq_len() ==> result === list.gpy_get(s).data.seq_item(index) & errors_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> gpy_ref_borrowed(1, list, result))]
+#[ensures(!(0 <= index && index < list.gpy_get(s).data.seq_len()) ==> result.is_null() & (s.error === GpyErrorState::Set(PytExceptionType::IndexError)))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pytlist_getitem(list: PytObjectPointer, index: pyt_ssize_t, s: &mut GpyGlobalState) -> PytObjectPointer {
+    PyList_GetItem(list, index)
+}
+
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(len >= 0)]
+#[ensures(gpy_gil_held(1))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(old(s)).ref_count == 0) & (result.gpy_get(s).ref_count == 1) & all_other_preserved(result, old(s), s) & !result.gpy_get(s).immortal)]
+#[ensures(!result.is_null() ==> result.gpy_get(s).data.is_tuple() && result.gpy_get(s).data.seq_len() == len && result.gpy_get(s).lent == 0)]
+#[ensures(!result.is_null() ==> forall(|i: pyt_ssize_t| (0 <= i && i < len ==> result.gpy_get(s).data.seq_item(i).is_null())))]
+#[ensures(result.is_null() ==> all_objects_preserved(old(s), s))]
+#[ensures(result.is_null() ==> (s.error === GpyErrorState::Set(PytExceptionType::MemoryError)))]
+#[ensures(!result.is_null() ==> errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pyttuple_new(len: pyt_ssize_t, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyTuple_New(len);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_new_ref(result, ShadowData::Tuple(len), true);
+    result
 }
 
 #[trusted]
 #[pure]
-#[requires(gpy_initialized(1))]
+#[requires(gpy_ref_held(1, tuple))]
+#[requires(tuple.gpy_get(s).data.is_tuple())]
+#[ensures(result == tuple.gpy_get(s).data.seq_len())]
+pub unsafe fn pyttuple_size(tuple: PytObjectPointer, s: &GpyGlobalState) -> pyt_ssize_t {
+    PyTuple_Size(tuple)
+}
+
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, tuple))]
+#[requires(tuple.gpy_get(s).data.is_tuple())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, tuple))]
+#[ensures(all_objects_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(0 <= index && index < tuple.gpy_get(s).data.seq_len() ==> result === tuple.gpy_get(s).data.seq_item(index) & errors_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> gpy_ref_borrowed(1, tuple, result))]
+#[ensures(!(0 <= index && index < tuple.gpy_get(s).data.seq_len()) ==> result.is_null() & (s.error === GpyErrorState::Set(PytExceptionType::IndexError)))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pyttuple_getitem(tuple: PytObjectPointer, index: pyt_ssize_t, s: &mut GpyGlobalState) -> PytObjectPointer {
+    PyTuple_GetItem(tuple, index)
+}
+
+// ints are accepted as well (converting them doesn't run Python code); one that is too large for a
+// double raises OverflowError. As with `pytlong_aslong`, -1.0 is a valid result, so the error
+// indicator must be clear beforehand
+#[trusted]
+#[requires(gpy_gil_held(1))]
 #[requires(gpy_ref_held(1, obj))]
-#[ensures(result == obj.gpy_get(s).data.is_bool())]
-pub unsafe fn pytbool_check(obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
-    MyBool_Check(obj) != 0
+#[requires(obj.gpy_get(s).data.is_float() || obj.gpy_get(s).data.is_long())]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj))]
+#[ensures(all_objects_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(obj.gpy_get(s).data.is_float() ==> result == obj.gpy_get(s).data.float_value() && !s.error.is_set())]
+#[ensures(obj.gpy_get(s).data.is_known_long() ==> result == obj.gpy_get(s).data.long_value() as f64 && !s.error.is_set())]
+#[ensures(s.error.is_set() ==> (result == -1.0) & (s.error === GpyErrorState::Set(PytExceptionType::OverflowError)))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pytfloat_asdouble(obj: PytObjectPointer, s: &mut GpyGlobalState) -> f64 {
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_use(obj);
+    let result = PyFloat_AsDouble(obj);
+    #[cfg(feature = "shadow-heap")]
+    if let Some(ShadowData::Float(v)) = s.shadow.get(obj) {
+        assert!(result == v || v.is_nan(), "shadow heap: object {:#x} is {} instead of {}", obj._private, result, v);
+    }
+    result
+}
+
+// copies `v`; empty and single-byte objects are cached by CPython, so the result need not be new
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & all_other_preserved(result, old(s), s) & ref_count_changed_by(result, 1, old(s), s))]
+#[ensures(!result.is_null() ==> result.gpy_get(s).data === GpyObjectData::PyBytes(v.len() as pyt_ssize_t))]
+#[ensures(!result.is_null() ==> (result.gpy_get(old(s)).ref_count == 0 || result.gpy_get(old(s)).data === GpyObjectData::PyBytes(v.len() as pyt_ssize_t)) && result.gpy_get(s).lent == result.gpy_get(old(s)).lent)]
+#[ensures(result.is_null() ==> all_objects_preserved(old(s), s))]
+#[ensures(result.is_null() ==> (s.error === GpyErrorState::Set(PytExceptionType::MemoryError)))]
+#[ensures(!result.is_null() ==> errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytbytes_fromslice(v: &[u8], s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyBytes_FromStringAndSize(v.as_ptr() as *const libc::c_char, v.len() as pyt_ssize_t);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_new_ref(result, ShadowData::Bytes(v.len() as pyt_ssize_t), false);
+    result
+}
+
+// bytes objects are immutable and the handle `obj` is borrowed from keeps it alive, so the slice
+// stays valid for as long as that borrow lasts (borrowing `s` wouldn't do: dropping a handle
+// releases its object without it). The type is checked at runtime, as the specs are gone in a
+// normal build and `PyBytes_AsString` on anything else returns NULL
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(result.is_some() <==> obj.ptr().gpy_get(s).data.is_bytes())]
+#[ensures(match result { Some(v) => v.len() == obj.ptr().gpy_get(s).data.bytes_len() as usize, None => true })]
+pub unsafe fn pytbytes_asslice<'a>(obj: handle::Borrowed<'a>, s: &GpyGlobalState) -> Option<&'a [u8]> {
+    if MyBytes_CheckExact(obj.ptr()) == 0 {
+        return None;
+    }
+    let len = PyBytes_Size(obj.ptr());
+    Some(std::slice::from_raw_parts(PyBytes_AsString(obj.ptr()) as *const u8, len as usize))
+}
+
+// does not steal the reference to `value`; the module takes a new one on success
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, module))]
+#[requires(gpy_ref_held(1, value))]
+#[requires(module !== value)]
+#[requires(module.gpy_get(s).data === GpyObjectData::PyModule)]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, module))]
+#[ensures(gpy_ref_held(1, value))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(module.gpy_get(s) === module.gpy_get(old(s)))]
+#[ensures(value.gpy_get(s).data === value.gpy_get(old(s)).data && value.gpy_get(s).lent == value.gpy_get(old(s)).lent)]
+#[ensures(result == 0 ==> ref_count_changed_by(value, 1, old(s), s) && errors_preserved(old(s), s))]
+#[ensures(result != 0 ==> (result == -1) & s.error.is_set() & (value.gpy_get(s).ref_count == value.gpy_get(old(s)).ref_count))]
+#[ensures(all_other_two_preserved(module, value, old(s), s))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pytmodule_addobjectref(module: PytObjectPointer, name: &CStr, value: PytObjectPointer, s: &mut GpyGlobalState) -> libc::c_int {
+    #[cfg(feature = "shadow-heap")]
+    let before = s.shadow_use(value);
+    let result = PyModule_AddObjectRef(module, name.as_ptr(), value);
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_changed_by(value, before, if result == 0 { 1 } else { 0 }, 0);
+        s.shadow_error();
+    }
+    result
 }
 
 #[trusted]
 #[pure]
-#[requires(gpy_initialized(1))]
 #[ensures(result <==> obj == s.constants.py_none)]
 pub unsafe fn pyt_isnone(obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
     My_IsNone(obj) != 0
@@ -364,7 +1314,6 @@ pub unsafe fn pyt_isnone(obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
 
 #[trusted]
 #[pure]
-#[requires(gpy_initialized(1))]
 #[ensures(result <==> obj == s.constants.py_true)]
 pub unsafe fn pyt_istrue(obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
     My_IsTrue(obj) != 0
@@ -372,7 +1321,6 @@ pub unsafe fn pyt_istrue(obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
 
 #[trusted]
 #[pure]
-#[requires(gpy_initialized(1))]
 #[ensures(result <==> obj == s.constants.py_false)]
 pub unsafe fn pyt_isfalse(obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
     My_IsFalse(obj) != 0
@@ -380,14 +1328,817 @@ pub unsafe fn pyt_isfalse(obj: PytObjectPointer, s: &GpyGlobalState) -> bool {
 
 #[trusted]
 #[pure]
-#[requires(gpy_initialized(1))]
 #[ensures(result <==> obj0 == obj1)]
-pub unsafe fn pyt_is(obj0: PytObjectPointer, obj1: PytObjectPointer) -> bool {
+pub unsafe fn pyt_is(obj0: PytObjectPointer, obj1: PytObjectPointer, s: &GpyGlobalState) -> bool {
     My_Is(obj0, obj1) != 0
 }
 
-/*
-pub unsafe fn pytobject_has_attr(obj0: PytObjectPointer, obj1: PytObjectPointer) -> bool {
+// the type object is borrowed from `obj`
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj))]
+#[ensures(gpy_ref_borrowed(1, obj, result))]
+#[ensures(result === obj.gpy_get(s).typ)]
+#[ensures(all_objects_preserved(old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pyt_type(obj: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    My_Type(obj)
+}
+
+#[trusted]
+#[pure]
+#[requires(gpy_ref_held(1, obj))]
+#[ensures(result <==> obj.gpy_get(s).typ === typ)]
+pub unsafe fn pyt_is_type(obj: PytObjectPointer, typ: PytObjectPointer, s: &GpyGlobalState) -> bool {
+    My_IsType(obj, typ) != 0
+}
+
+// the values match CPython's Py_LT, ..., Py_GE
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PytCompareOp {
+    Lt = 0,
+    Le = 1,
+    Eq = 2,
+    Ne = 3,
+    Gt = 4,
+    Ge = 5,
+}
+
+impl PytCompareOp {
+    #[pure]
+    pub fn holds(&self, a: libc::c_long, b: libc::c_long) -> bool {
+        match self {
+            PytCompareOp::Lt => a < b,
+            PytCompareOp::Le => a <= b,
+            PytCompareOp::Eq => a == b,
+            PytCompareOp::Ne => a != b,
+            PytCompareOp::Gt => a > b,
+            PytCompareOp::Ge => a >= b,
+        }
+    }
+}
+
+// calling Python code may do anything to the heap except for what `gpy_havoc` promises; the
+// objects we pass in stay alive because we hold references to them
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, callable))]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, callable))]
+#[ensures(callable.gpy_get(s).ref_count >= 1)]
+#[ensures(gpy_havoc(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(s).ref_count >= 1) & !s.error.is_set())]
+#[ensures(result.is_null() ==> s.error.is_set())]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytobject_callnoargs(callable: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyObject_CallNoArgs(callable);
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_havoc();
+        s.shadow_new_ref(result, ShadowData::Other, false);
+    }
+    result
+}
+
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, callable))]
+#[requires(gpy_ref_held(1, arg))]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, callable))]
+#[ensures(gpy_ref_held(1, arg))]
+#[ensures(callable.gpy_get(s).ref_count >= 1 && arg.gpy_get(s).ref_count >= 1)]
+#[ensures(gpy_havoc(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(s).ref_count >= 1) & !s.error.is_set())]
+#[ensures(result.is_null() ==> s.error.is_set())]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytobject_callonearg(callable: PytObjectPointer, arg: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyObject_CallOneArg(callable, arg);
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_havoc();
+        s.shadow_new_ref(result, ShadowData::Other, false);
+    }
+    result
+}
+
+// `args` is a tuple of the positional arguments, or NULL when there are none
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, callable))]
+#[requires(!args.is_null() ==> gpy_ref_held(1, args))]
+#[requires(!args.is_null() ==> args.gpy_get(s).data.is_tuple())]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, callable))]
+#[ensures(!args.is_null() ==> gpy_ref_held(1, args))]
+#[ensures(callable.gpy_get(s).ref_count >= 1)]
+#[ensures(!args.is_null() ==> args.gpy_get(s).ref_count >= 1)]
+#[ensures(gpy_havoc(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(s).ref_count >= 1) & !s.error.is_set())]
+#[ensures(result.is_null() ==> s.error.is_set())]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytobject_callobject(callable: PytObjectPointer, args: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyObject_CallObject(callable, args);
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_havoc();
+        s.shadow_new_ref(result, ShadowData::Other, false);
+    }
+    result
+}
+
+// `__iter__` may run arbitrary code
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj))]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj))]
+#[ensures(obj.gpy_get(s).ref_count >= 1)]
+#[ensures(gpy_havoc(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(s).ref_count >= 1) & !s.error.is_set())]
+#[ensures(result.is_null() ==> s.error.is_set())]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytobject_getiter(obj: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyObject_GetIter(obj);
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_havoc();
+        s.shadow_new_ref(result, ShadowData::Other, false);
+    }
+    result
+}
+
+// NULL both when the iterator is exhausted (no error set) and when `__next__` failed (error set)
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, iter))]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, iter))]
+#[ensures(iter.gpy_get(s).ref_count >= 1)]
+#[ensures(gpy_havoc(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(s).ref_count >= 1) & !s.error.is_set())]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytiter_next(iter: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyIter_Next(iter);
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_havoc();
+        if result.is_null() {
+            s.shadow_error();
+        } else {
+            s.shadow_new_ref(result, ShadowData::Other, false);
+        }
+    }
+    result
+}
 
+// unlike `pytobject_getattr`, the name isn't an object we can look at, so this is specified like a
+// call into Python code
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj))]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj))]
+#[ensures(obj.gpy_get(s).ref_count >= 1)]
+#[ensures(gpy_havoc(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(s).ref_count >= 1) & !s.error.is_set())]
+#[ensures(result.is_null() ==> s.error.is_set())]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytobject_getattrstring(obj: PytObjectPointer, name: &CStr, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyObject_GetAttrString(obj, name.as_ptr());
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_havoc();
+        s.shadow_new_ref(result, ShadowData::Other, false);
+    }
+    result
+}
+
+// importing runs the module's code
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_havoc(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(s).ref_count >= 1) & !s.error.is_set())]
+#[ensures(result.is_null() ==> s.error.is_set())]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytimport_importmodule(name: &CStr, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyImport_ImportModule(name.as_ptr());
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_havoc();
+        s.shadow_new_ref(result, ShadowData::Module, false);
+    }
+    result
+}
+
+// exceptions are printed and cleared by CPython, so the error indicator stays clear either way
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_havoc(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!s.error.is_set())]
+#[ensures(result == 0 || result == -1)]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pyrun_simplestring(command: &CStr, s: &mut GpyGlobalState) -> libc::c_int {
+    let result = PyRun_SimpleString(command.as_ptr());
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_havoc();
+        s.shadow_error();
+    }
+    result
+}
+
+// two ints are compared without running Python code, into True or False (it can still fail, with
+// RecursionError); comparing other objects can run arbitrary Python code and return any object
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj0))]
+#[requires(gpy_ref_held(1, obj1))]
+#[requires(!(obj0.gpy_get(s).data.is_long() && obj1.gpy_get(s).data.is_long()) ==> gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj0))]
+#[ensures(gpy_ref_held(1, obj1))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(s).ref_count >= 1) & !s.error.is_set())]
+#[ensures(result.is_null() ==> s.error.is_set())]
+#[ensures(obj0.gpy_get(old(s)).data.is_long() && obj1.gpy_get(old(s)).data.is_long() && !result.is_null() ==>
+    (result === s.constants.py_true || result === s.constants.py_false)
+    & ref_count_changed_by(result, 1, old(s), s)
+    & (result.gpy_get(s).data === result.gpy_get(old(s)).data) & (result.gpy_get(s).lent == result.gpy_get(old(s)).lent)
+    & all_other_preserved(result, old(s), s))]
+#[ensures(obj0.gpy_get(old(s)).data.is_known_long() && obj1.gpy_get(old(s)).data.is_known_long() && !result.is_null() ==>
+    (result === s.constants.py_true <==> op.holds(obj0.gpy_get(old(s)).data.long_value(), obj1.gpy_get(old(s)).data.long_value())))]
+#[ensures(obj0.gpy_get(old(s)).data.is_long() && obj1.gpy_get(old(s)).data.is_long() && result.is_null() ==> all_objects_preserved(old(s), s))]
+#[ensures(!(obj0.gpy_get(old(s)).data.is_long() && obj1.gpy_get(old(s)).data.is_long()) ==>
+    gpy_havoc(old(s), s) & (obj0.gpy_get(s).ref_count >= 1) & (obj1.gpy_get(s).ref_count >= 1))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytobject_richcompare(obj0: PytObjectPointer, obj1: PytObjectPointer, op: PytCompareOp, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyObject_RichCompare(obj0, obj1, op as libc::c_int);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_compare_result(op, obj0, obj1, result);
+    result
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GpyNumberOp {
+    Add,
+    Subtract,
+    Multiply,
+    FloorDivide,
+    Power,
+}
+
+impl GpyNumberOp {
+    // the value of `a op b` if it is an int representable as a C long (Python ints never overflow)
+    #[pure]
+    fn apply(&self, a: libc::c_long, b: libc::c_long) -> GpyLongData {
+        match self {
+            GpyNumberOp::Add => gpy_long_from_wide(a as i128 + b as i128),
+            GpyNumberOp::Subtract => gpy_long_from_wide(a as i128 - b as i128),
+            GpyNumberOp::Multiply => gpy_long_from_wide(a as i128 * b as i128),
+            GpyNumberOp::FloorDivide => if b == 0 { GpyLongData::Unknown } else { gpy_long_from_wide(gpy_floor_div(a as i128, b as i128)) },
+            GpyNumberOp::Power => if b < 0 { GpyLongData::Unknown } else { gpy_pow(a, b) },
+        }
+    }
+
+    // `a // 0` and `0 ** -n` raise ZeroDivisionError
+    #[pure]
+    fn divides_by_zero(&self, a: libc::c_long, b: libc::c_long) -> bool {
+        match self {
+            GpyNumberOp::FloorDivide => b == 0,
+            GpyNumberOp::Power => a == 0 && b < 0,
+            _ => false,
+        }
+    }
+}
+
+#[pure]
+fn gpy_long_from_wide(v: i128) -> GpyLongData {
+    if libc::c_long::MIN as i128 <= v && v <= libc::c_long::MAX as i128 {
+        GpyLongData::Known(v as libc::c_long)
+    } else {
+        GpyLongData::Unknown
+    }
+}
+
+// rounds towards negative infinity like Python's `//`, unlike Rust's `/`
+#[pure]
+#[requires(b != 0)]
+fn gpy_floor_div(a: i128, b: i128) -> i128 {
+    if a % b != 0 && ((a < 0) != (b < 0)) {
+        a / b - 1
+    } else {
+        a / b
+    }
+}
+
+// once an intermediate power doesn't fit, neither does the result (unless |a| <= 1, where none
+// of them overflow)
+#[pure]
+#[requires(b >= 0)]
+fn gpy_pow(a: libc::c_long, b: libc::c_long) -> GpyLongData {
+    if b == 0 {
+        GpyLongData::Known(1)
+    } else {
+        match gpy_pow(a, b - 1) {
+            GpyLongData::Known(p) => gpy_long_from_wide(p as i128 * a as i128),
+            GpyLongData::Unknown => GpyLongData::Unknown,
+        }
+    }
+}
+
+// what `obj0 op obj1` does for two ints: no Python code runs, the result is a new reference to a
+// (possibly cached) int, and only the power of a negative exponent can give something else (a float).
+// The operands may be the same object; the wrappers only look at them, but each operand still needs
+// a reference of its own (`gpy_ref_held(2, obj)` for `x * x`)
+predicate! {
+    fn gpy_number_result(op: GpyNumberOp, obj0: PytObjectPointer, obj1: PytObjectPointer, result: PytObjectPointer, s0: &GpyGlobalState, s: &GpyGlobalState) -> bool {
+        (!result.is_null() ==> all_other_preserved(result, s0, s) && errors_preserved(s0, s)
+            && ref_count_changed_by(result, 1, s0, s) && result.gpy_get(s).lent == result.gpy_get(s0).lent
+            && (result.gpy_get(s0).ref_count == 0 || result.gpy_get(s0).data.is_long()))
+        && (!result.is_null() && (op != GpyNumberOp::Power || (obj1.gpy_get(s0).data.is_known_long() && obj1.gpy_get(s0).data.long_value() >= 0))
+            ==> result.gpy_get(s).data.is_long())
+        && (!result.is_null() && obj0.gpy_get(s0).data.is_known_long() && obj1.gpy_get(s0).data.is_known_long()
+            && !(op == GpyNumberOp::Power && obj1.gpy_get(s0).data.long_value() < 0)
+            ==> result.gpy_get(s).data === GpyObjectData::PyLong(op.apply(obj0.gpy_get(s0).data.long_value(), obj1.gpy_get(s0).data.long_value())))
+        && (result.is_null() ==> all_objects_preserved(s0, s) && s.error.is_set())
+        && (obj0.gpy_get(s0).data.is_known_long() && obj1.gpy_get(s0).data.is_known_long()
+            && op.divides_by_zero(obj0.gpy_get(s0).data.long_value(), obj1.gpy_get(s0).data.long_value())
+            ==> result.is_null() && s.error === GpyErrorState::Set(PytExceptionType::ZeroDivisionError))
+    }
+}
+
+// only specified for ints so far (other operands can run arbitrary `__add__` etc.); NULL on
+// MemoryError
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj0))]
+#[requires(gpy_ref_held(1, obj1))]
+#[requires(obj0.gpy_get(s).data.is_long() && obj1.gpy_get(s).data.is_long())]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj0))]
+#[ensures(gpy_ref_held(1, obj1))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+#[ensures(gpy_number_result(GpyNumberOp::Add, obj0, obj1, result, old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+pub unsafe fn pytnumber_add(obj0: PytObjectPointer, obj1: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyNumber_Add(obj0, obj1);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_number_result(GpyNumberOp::Add, obj0, obj1, result);
+    result
+}
+
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj0))]
+#[requires(gpy_ref_held(1, obj1))]
+#[requires(obj0.gpy_get(s).data.is_long() && obj1.gpy_get(s).data.is_long())]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj0))]
+#[ensures(gpy_ref_held(1, obj1))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+#[ensures(gpy_number_result(GpyNumberOp::Subtract, obj0, obj1, result, old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+pub unsafe fn pytnumber_subtract(obj0: PytObjectPointer, obj1: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyNumber_Subtract(obj0, obj1);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_number_result(GpyNumberOp::Subtract, obj0, obj1, result);
+    result
+}
+
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj0))]
+#[requires(gpy_ref_held(1, obj1))]
+#[requires(obj0.gpy_get(s).data.is_long() && obj1.gpy_get(s).data.is_long())]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj0))]
+#[ensures(gpy_ref_held(1, obj1))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+#[ensures(gpy_number_result(GpyNumberOp::Multiply, obj0, obj1, result, old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+pub unsafe fn pytnumber_multiply(obj0: PytObjectPointer, obj1: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyNumber_Multiply(obj0, obj1);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_number_result(GpyNumberOp::Multiply, obj0, obj1, result);
+    result
+}
+
+// NULL with a ZeroDivisionError for `x // 0`
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj0))]
+#[requires(gpy_ref_held(1, obj1))]
+#[requires(obj0.gpy_get(s).data.is_long() && obj1.gpy_get(s).data.is_long())]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj0))]
+#[ensures(gpy_ref_held(1, obj1))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+#[ensures(gpy_number_result(GpyNumberOp::FloorDivide, obj0, obj1, result, old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+pub unsafe fn pytnumber_floordivide(obj0: PytObjectPointer, obj1: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyNumber_FloorDivide(obj0, obj1);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_number_result(GpyNumberOp::FloorDivide, obj0, obj1, result);
+    result
+}
+
+// `pow(obj0, obj1)` without a modulus; a negative exponent gives a float, `0 ** -n` raises
+// ZeroDivisionError
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj0))]
+#[requires(gpy_ref_held(1, obj1))]
+#[requires(obj0.gpy_get(s).data.is_long() && obj1.gpy_get(s).data.is_long())]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj0))]
+#[ensures(gpy_ref_held(1, obj1))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+#[ensures(gpy_number_result(GpyNumberOp::Power, obj0, obj1, result, old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+pub unsafe fn pytnumber_power(obj0: PytObjectPointer, obj1: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyNumber_Power(obj0, obj1, MyNone_GetNoIncRef());
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_number_result(GpyNumberOp::Power, obj0, obj1, result);
+    result
+}
+
+// bools, None and ints are handled without calling into Python code; anything else may run an
+// arbitrary `__bool__` or `__len__`
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(-1 <= result && result <= 1)]
+#[ensures(result == -1 <==> s.error.is_set())]
+#[ensures(old(obj === s.constants.py_true) ==> result == 1)]
+#[ensures(old(obj === s.constants.py_false || obj === s.constants.py_none) ==> result == 0)]
+#[ensures(obj.gpy_get(old(s)).data.is_known_long() ==> (result == 1 <==> obj.gpy_get(old(s)).data.long_value() != 0))]
+#[ensures(obj.gpy_get(old(s)).data.is_bool() || obj.gpy_get(old(s)).data.is_long() || old(obj === s.constants.py_none) ==>
+    all_objects_preserved(old(s), s) & !s.error.is_set())]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pytobject_istrue(obj: PytObjectPointer, s: &mut GpyGlobalState) -> libc::c_int {
+    let result = PyObject_IsTrue(obj);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_error();
+    result
+}
+
+// a plain lookup for the types `gpy_getattr` knows; for the others it is specified like a call into
+// Python code
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj))]
+#[requires(gpy_ref_held(1, name))]
+#[requires(name.gpy_get(s).data === GpyObjectData::PyUnicode)]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj))]
+#[ensures(gpy_ref_held(1, name))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(s).ref_count >= 1) & !s.error.is_set())]
+#[ensures(result.is_null() ==> s.error.is_set())]
+#[ensures(!gpy_attr_runs_code(obj, old(s)) ==> (result === gpy_getattr(obj, name, old(s)).0) & (s.error === gpy_getattr(obj, name, old(s)).1))]
+#[ensures(!gpy_attr_runs_code(obj, old(s)) && !result.is_null() ==> ref_count_changed_by(result, 1, old(s), s)
+    & (result.gpy_get(s).data === result.gpy_get(old(s)).data) & (result.gpy_get(s).lent == result.gpy_get(old(s)).lent)
+    & all_other_preserved(result, old(s), s))]
+#[ensures(!gpy_attr_runs_code(obj, old(s)) && result.is_null() ==> all_objects_preserved(old(s), s))]
+#[ensures(gpy_attr_runs_code(obj, old(s)) ==> gpy_havoc(old(s), s) & (obj.gpy_get(s).ref_count >= 1) & (name.gpy_get(s).ref_count >= 1))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytobject_getattr(obj: PytObjectPointer, name: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = PyObject_GetAttr(obj, name);
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_attr_access(obj);
+        s.shadow_new_ref(result, ShadowData::Other, false);
+    }
+    result
+}
+
+// what happens to the previous value of the attribute (and to `value`'s reference count) is up to
+// the type, so nothing is promised about the other objects; for the types `gpy_setattr` doesn't
+// know, any Python code may run
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj))]
+#[requires(gpy_ref_held(1, name))]
+#[requires(gpy_ref_held(1, value))]
+#[requires(name.gpy_get(s).data === GpyObjectData::PyUnicode)]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj))]
+#[ensures(gpy_ref_held(1, name))]
+#[ensures(gpy_ref_held(1, value))]
+#[ensures(!gpy_attr_runs_code(obj, old(s)) ==> (obj.gpy_get(s).data === gpy_setattr(obj, name, value, old(s)).0)
+    & (obj.gpy_get(s).ref_count == obj.gpy_get(old(s)).ref_count) & (s.error === gpy_setattr(obj, name, value, old(s)).1))]
+#[ensures(gpy_attr_runs_code(obj, old(s)) ==> gpy_havoc(old(s), s) & (obj.gpy_get(s).ref_count >= 1))]
+#[ensures(obj.gpy_get(s).lent == 0)]
+#[ensures(result == -1 <==> s.error.is_set())]
+#[ensures(result == 0 || result == -1)]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pytobject_setattr(obj: PytObjectPointer, name: PytObjectPointer, value: PytObjectPointer, s: &mut GpyGlobalState) -> libc::c_int {
+    let result = PyObject_SetAttr(obj, name, value);
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_attr_access(obj);
+        s.shadow_error();
+    }
+    result
+}
+
+// any exception raised while looking the attribute up is swallowed, so this doesn't touch the error
+// indicator; the lookup itself is the same as `pytobject_getattr`'s, Python code and all
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj))]
+#[requires(gpy_ref_held(1, name))]
+#[requires(name.gpy_get(s).data === GpyObjectData::PyUnicode)]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj))]
+#[ensures(gpy_ref_held(1, name))]
+#[ensures(!s.error.is_set())]
+#[ensures(!gpy_attr_runs_code(obj, old(s)) ==> (result <==> !gpy_getattr(obj, name, old(s)).0.is_null()) & all_objects_preserved(old(s), s))]
+#[ensures(gpy_attr_runs_code(obj, old(s)) ==> gpy_havoc(old(s), s) & (obj.gpy_get(s).ref_count >= 1) & (name.gpy_get(s).ref_count >= 1))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pytobject_hasattr(obj: PytObjectPointer, name: PytObjectPointer, s: &mut GpyGlobalState) -> bool {
+    let result = PyObject_HasAttr(obj, name) != 0;
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_attr_access(obj);
+        s.shadow_error();
+    }
+    result
+}
+
+// the exception taken out of the error indicator by `pyt_err_fetch`; owns the references to its
+// parts (counted in `refs_held`) until it is handed back with `pyt_err_restore`
+#[must_use]
+pub struct PytFetchedError {
+    typ: PytObjectPointer,
+    value: PytObjectPointer,
+    traceback: PytObjectPointer,
+    error: GpyErrorState, // spec-only
+    refs: pyt_ssize_t, // spec-only: how many of the parts are set
+}
+
+impl PytFetchedError {
+    #[pure]
+    pub fn is_set(&self) -> bool {
+        self.error.is_set()
+    }
+}
+
+#[trusted]
+#[pure]
+#[ensures(result <==> s.error.is_set())]
+pub unsafe fn pyt_err_occurred(s: &GpyGlobalState) -> bool {
+    !PyErr_Occurred().is_null()
+}
+
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(!s.error.is_set())]
+#[ensures(all_objects_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pyt_err_clear(s: &mut GpyGlobalState) {
+    PyErr_Clear();
+    #[cfg(feature = "shadow-heap")]
+    assert!(!s.shadow_error().is_set(), "shadow heap: PyErr_Clear left an error set");
+}
+
+// replaces the current error (if any)
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(exc != PytExceptionType::Other)]
+#[ensures(gpy_gil_held(1))]
+#[ensures(s.error === GpyErrorState::Set(exc))]
+#[ensures(all_objects_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pyt_err_setstring(exc: PytExceptionType, message: &CStr, s: &mut GpyGlobalState) {
+    PyErr_SetString(exc.as_ptr(), message.as_ptr());
+    #[cfg(feature = "shadow-heap")]
+    assert!(s.shadow_error() == GpyErrorState::Set(exc), "shadow heap: PyErr_SetString set a different error");
+}
+
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_error_fetched(1))]
+#[ensures(result.error === old(s.error))]
+#[ensures(!s.error.is_set())]
+#[ensures(all_objects_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(0 <= result.refs && result.refs <= 3 && (!result.error.is_set() ==> result.refs == 0))]
+#[ensures(refs_held_changed_by(result.refs, old(s), s))]
+pub unsafe fn pyt_err_fetch(s: &mut GpyGlobalState) -> PytFetchedError {
+    let mut typ = PytObjectPointer { _private: 0 };
+    let mut value = PytObjectPointer { _private: 0 };
+    let mut traceback = PytObjectPointer { _private: 0 };
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_error();
+    let error = s.error;
+    PyErr_Fetch(&mut typ, &mut value, &mut traceback);
+    let refs = [typ, value, traceback].iter().filter(|obj| !obj.is_null()).count() as pyt_ssize_t;
+    #[cfg(feature = "shadow-heap")]
+    {
+        assert!(!s.shadow_error().is_set(), "shadow heap: PyErr_Fetch left an error set");
+        s.refs_held += refs;
+    }
+    PytFetchedError {
+        typ,
+        value,
+        traceback,
+        error,
+        refs,
+    }
+}
+
+// steals the references held by `err`
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_error_fetched(1))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(s.error === err.error)]
+#[ensures(all_objects_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_changed_by(-err.refs, old(s), s))]
+pub unsafe fn pyt_err_restore(err: PytFetchedError, s: &mut GpyGlobalState) {
+    PyErr_Restore(err.typ, err.value, err.traceback);
+    #[cfg(feature = "shadow-heap")]
+    {
+        assert!(s.shadow_error() == err.error, "shadow heap: PyErr_Restore set a different error");
+        s.refs_held -= err.refs;
+    }
+}
+
+#[trusted]
+#[pure]
+#[requires(exc != PytExceptionType::Other)]
+#[ensures(s.error === GpyErrorState::Set(exc) ==> result)]
+#[ensures(result ==> s.error.is_set())]
+#[ensures(forall(|t: PytExceptionType| (s.error === GpyErrorState::Set(t) && t != exc && t != PytExceptionType::Other ==> !result)))]
+pub unsafe fn pyt_err_exceptionmatches(exc: PytExceptionType, s: &GpyGlobalState) -> bool {
+    PyErr_ExceptionMatches(exc.as_ptr()) != 0
+}
+
+const PYBUF_SIMPLE: libc::c_int = 0;
+
+// `Py_buffer`; holds a reference to the exporter until it is released, so the exporter (and the
+// memory behind `as_slice`) stays alive for as long as the buffer exists. Only read-only views are
+// handed out, so nothing (not even Python code the wrappers run) writes to that memory meanwhile
+#[repr(C)]
+pub struct PytBuffer {
+    buf: *mut libc::c_void,
+    obj: PytObjectPointer,
+    len: pyt_ssize_t,
+    itemsize: pyt_ssize_t,
+    readonly: libc::c_int,
+    ndim: libc::c_int,
+    format: *mut libc::c_char,
+    shape: *mut pyt_ssize_t,
+    strides: *mut pyt_ssize_t,
+    suboffsets: *mut pyt_ssize_t,
+    internal: *mut libc::c_void,
+}
+
+impl PytBuffer {
+    #[pure]
+    pub fn exporter(&self) -> PytObjectPointer {
+        self.obj
+    }
+
+    #[pure]
+    pub fn len(&self) -> pyt_ssize_t {
+        self.len
+    }
+
+    // the slice borrows the buffer, so it can't outlive `pytbuffer_release`
+    #[trusted]
+    #[ensures(result.len() == self.len() as usize)]
+    pub fn as_slice(&self) -> &[u8] {
+        if self.len == 0 {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(self.buf as *const u8, self.len as usize) }
+        }
+    }
+}
+
+// a simple (contiguous, read-only) buffer of `obj`'s bytes; fails with an error set if `obj`
+// doesn't support the buffer protocol, or if it only exports writable views (`PyBUF_SIMPLE` lets
+// a `bytearray` hand out its memory as it is, which could change under `as_slice`)
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, obj))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, obj))]
+#[ensures(match &result {
+    Some(b) => gpy_buffer_held(1, obj) & (b.exporter() === obj) & (b.len() >= 0) & ref_count_changed_by(obj, 1, old(s), s)
+        & all_other_preserved(obj, old(s), s) & errors_preserved(old(s), s),
+    None => all_objects_preserved(old(s), s) && s.error.is_set(),
+})]
+#[ensures(obj.gpy_get(s).data === obj.gpy_get(old(s)).data && obj.gpy_get(s).lent == obj.gpy_get(old(s)).lent)]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pytobject_getbuffer(obj: PytObjectPointer, s: &mut GpyGlobalState) -> Option<PytBuffer> {
+    let mut view = std::mem::MaybeUninit::<PytBuffer>::uninit();
+    #[cfg(feature = "shadow-heap")]
+    let before = s.shadow_use(obj);
+    let mut result = PyObject_GetBuffer(obj, view.as_mut_ptr(), PYBUF_SIMPLE);
+    if result == 0 && (*view.as_ptr()).readonly == 0 {
+        PyBuffer_Release(view.as_mut_ptr());
+        PyErr_SetString(PyExc_BufferError, c"a read-only buffer is required".as_ptr());
+        result = -1;
+    }
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_changed_by(obj, before, if result == 0 { 1 } else { 0 }, 0);
+        assert!(s.shadow_error().is_set() == (result != 0), "shadow heap: PyObject_GetBuffer failed without an error set");
+    }
+    if result == 0 {
+        Some(view.assume_init())
+    } else {
+        None
+    }
+}
+
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_buffer_held(1, buf.exporter()))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(ref_count_changed_by(old(buf.exporter()), -1, old(s), s))]
+#[ensures(old(buf.exporter()).gpy_get(s).lent == old(buf.exporter()).gpy_get(old(s)).lent)]
+#[ensures(all_other_preserved(old(buf.exporter()), old(s), s))]
+#[ensures(errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(refs_held_preserved(old(s), s))]
+pub unsafe fn pytbuffer_release(mut buf: PytBuffer, s: &mut GpyGlobalState) {
+    #[cfg(feature = "shadow-heap")]
+    let (exporter, before) = (buf.exporter(), s.shadow_use(buf.exporter()));
+    PyBuffer_Release(&mut buf);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_changed_by(exporter, before, -1, 0);
 }
-*/
@@ -0,0 +1,143 @@
+// Extension modules: Rust functions that Python code can import and call. A function is a type
+// implementing `PytFunctionNoArgs`, `PytFunctionO` or `PytFunctionVarArgs`, whose specs are what
+// CPython expects from a `PyCFunction`: the arguments are only lent to it and the result is a new
+// reference or NULL with an error set. A function that verifies against them has balanced
+// refcounts as seen from Python.
+//
+// CPython calls the (unverified) trampolines below, which hand the function a fresh
+// `GpyGlobalState`; the interpreter holds the GIL and has no error set whenever it calls one.
+
+use prusti_contracts::*;
+
+use super::*;
+
+pub(super) const METH_VARARGS: libc::c_int = 0x0001;
+pub(super) const METH_NOARGS: libc::c_int = 0x0004;
+pub(super) const METH_O: libc::c_int = 0x0008;
+
+type PytCFunction = unsafe extern "C" fn(PytObjectPointer, PytObjectPointer) -> PytObjectPointer;
+
+pub trait PytFunctionNoArgs {
+    #[requires(gpy_gil_held(1))]
+    #[requires(gpy_ref_held(1, module))]
+    #[requires(gpy_no_borrows_active(s))]
+    #[requires(!s.error.is_set())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(gpy_ref_held(1, module))]
+    #[ensures(!result.is_null() ==> gpy_ref_held(1, result) & !s.error.is_set() & refs_held_changed_by(1, old(s), s))]
+    #[ensures(result.is_null() ==> s.error.is_set() && gpy_refs_balanced(old(s), s))]
+    unsafe fn call(module: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer;
+}
+
+pub trait PytFunctionO {
+    #[requires(gpy_gil_held(1))]
+    #[requires(gpy_ref_held(1, module))]
+    #[requires(gpy_ref_held(1, arg))]
+    #[requires(gpy_no_borrows_active(s))]
+    #[requires(!s.error.is_set())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(gpy_ref_held(1, module))]
+    #[ensures(gpy_ref_held(1, arg))]
+    #[ensures(!result.is_null() ==> gpy_ref_held(1, result) & !s.error.is_set() & refs_held_changed_by(1, old(s), s))]
+    #[ensures(result.is_null() ==> s.error.is_set() && gpy_refs_balanced(old(s), s))]
+    unsafe fn call(module: PytObjectPointer, arg: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer;
+}
+
+// `args` is the tuple of positional arguments
+pub trait PytFunctionVarArgs {
+    #[requires(gpy_gil_held(1))]
+    #[requires(gpy_ref_held(1, module))]
+    #[requires(gpy_ref_held(1, args))]
+    #[requires(pyttuple_checkexact(args, s))]
+    #[requires(gpy_no_borrows_active(s))]
+    #[requires(!s.error.is_set())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(gpy_ref_held(1, module))]
+    #[ensures(gpy_ref_held(1, args))]
+    #[ensures(!result.is_null() ==> gpy_ref_held(1, result) & !s.error.is_set() & refs_held_changed_by(1, old(s), s))]
+    #[ensures(result.is_null() ==> s.error.is_set() && gpy_refs_balanced(old(s), s))]
+    unsafe fn call(module: PytObjectPointer, args: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer;
+}
+
+// fills in a freshly created module, e.g. adds the types created with `pyttype_create`
+pub trait PytModuleExec {
+    #[requires(gpy_gil_held(1))]
+    #[requires(gpy_ref_held(1, module))]
+    #[requires(module.gpy_get(s).data === GpyObjectData::PyModule)]
+    #[requires(gpy_no_borrows_active(s))]
+    #[requires(!s.error.is_set())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(gpy_ref_held(1, module))]
+    #[ensures(result ==> !s.error.is_set())]
+    #[ensures(!result ==> s.error.is_set())]
+    #[ensures(gpy_refs_balanced(old(s), s))]
+    unsafe fn exec(module: PytObjectPointer, s: &mut GpyGlobalState) -> bool;
+}
+
+#[trusted]
+unsafe extern "C" fn gpy_call_noargs<F: PytFunctionNoArgs>(module: PytObjectPointer, _arg: PytObjectPointer) -> PytObjectPointer {
+    F::call(module, &mut gpy_new_state())
+}
+
+#[trusted]
+unsafe extern "C" fn gpy_call_o<F: PytFunctionO>(module: PytObjectPointer, arg: PytObjectPointer) -> PytObjectPointer {
+    F::call(module, arg, &mut gpy_new_state())
+}
+
+#[trusted]
+unsafe extern "C" fn gpy_call_varargs<F: PytFunctionVarArgs>(module: PytObjectPointer, args: PytObjectPointer) -> PytObjectPointer {
+    F::call(module, args, &mut gpy_new_state())
+}
+
+// `PyMethodDef`; build a table of these in a `static` and pass it to `pytmodule_create`
+#[repr(C)]
+pub struct PytMethodDef {
+    pub(super) ml_name: *const libc::c_char,
+    pub(super) ml_meth: PytCFunction,
+    pub(super) ml_flags: libc::c_int,
+    pub(super) ml_doc: *const libc::c_char,
+}
+
+// the tables are never written to after they are built
+unsafe impl Sync for PytMethodDef {}
+
+impl PytMethodDef {
+    #[trusted]
+    pub const fn noargs<F: PytFunctionNoArgs>(name: &'static CStr, doc: &'static CStr) -> PytMethodDef {
+        PytMethodDef { ml_name: name.as_ptr(), ml_meth: gpy_call_noargs::<F>, ml_flags: METH_NOARGS, ml_doc: doc.as_ptr() }
+    }
+
+    #[trusted]
+    pub const fn o<F: PytFunctionO>(name: &'static CStr, doc: &'static CStr) -> PytMethodDef {
+        PytMethodDef { ml_name: name.as_ptr(), ml_meth: gpy_call_o::<F>, ml_flags: METH_O, ml_doc: doc.as_ptr() }
+    }
+
+    #[trusted]
+    pub const fn varargs<F: PytFunctionVarArgs>(name: &'static CStr, doc: &'static CStr) -> PytMethodDef {
+        PytMethodDef { ml_name: name.as_ptr(), ml_meth: gpy_call_varargs::<F>, ml_flags: METH_VARARGS, ml_doc: doc.as_ptr() }
+    }
+}
+
+extern "C" {
+    fn MyModule_Create(name: *const libc::c_char, doc: *const libc::c_char, methods: *const PytMethodDef, count: pyt_ssize_t) -> PytObjectPointer;
+}
+
+// the body of `PyInit_<name>`: builds the `PyModuleDef` (with `methods` plus the terminating
+// sentinel) and creates the module from it. The interpreter is up already since it is importing
+// us, so handles may release their references from here on.
+#[trusted]
+pub unsafe fn pytmodule_create(name: &'static CStr, doc: &'static CStr, methods: &'static [PytMethodDef]) -> PytObjectPointer {
+    gpy_interpreter_started();
+    MyModule_Create(name.as_ptr(), doc.as_ptr(), methods.as_ptr(), methods.len() as pyt_ssize_t)
+}
+
+// like `pytmodule_create`, but runs `E::exec` on the new module before handing it out
+#[trusted]
+pub unsafe fn pytmodule_create_exec<E: PytModuleExec>(name: &'static CStr, doc: &'static CStr, methods: &'static [PytMethodDef]) -> PytObjectPointer {
+    let module = pytmodule_create(name, doc, methods);
+    if !module.is_null() && !E::exec(module, &mut gpy_new_state()) {
+        Py_DecRef(module);
+        return PytObjectPointer { _private: 0 };
+    }
+    module
+}
@@ -0,0 +1,409 @@
+// Safe handles on top of the `pyt_*` functions. An `Owned` holds one reference to its object and
+// gives it back when dropped, so application code never touches `pyt_incref`/`pyt_decref`.
+//
+// The reference held by a handle is not a `gpy_ref_held` obligation any more: `Owned::from_raw`
+// takes the obligation over and `Owned::into_raw` hands it back out. `Clone` and `Drop` get no
+// `GpyGlobalState`, so the model doesn't see them. A clone only adds a reference, but dropping a
+// handle can free its object, whose finalizer (`__del__`) may then run arbitrary Python code: a
+// drop counts as a `gpy_havoc` of the state. Code that needs the ghost data of mutable objects
+// to survive gives handles back with `Owned::release` instead, whose spec says as much.
+
+use std::marker::PhantomData;
+
+use prusti_contracts::*;
+
+use super::*;
+
+pub struct Owned {
+    ptr: PytObjectPointer,
+    interpreter: usize, // the `INTERPRETER` the object lives in
+}
+
+// a reference that is kept alive by an `Owned` for as long as `'a`
+#[derive(Clone, Copy)]
+pub struct Borrowed<'a> {
+    ptr: PytObjectPointer,
+    _owner: PhantomData<&'a Owned>,
+}
+
+impl Owned {
+    #[trusted]
+    #[ensures(result.ptr() === ptr)]
+    fn wrap(ptr: PytObjectPointer) -> Owned {
+        Owned { ptr, interpreter: INTERPRETER.load(Ordering::SeqCst) }
+    }
+
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[requires(gpy_ref_held(1, ptr))]
+    #[requires(!ptr.is_null())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(result.ptr() === ptr)]
+    #[ensures(refs_held_changed_by(-1, old(s), s))]
+    #[ensures(all_objects_preserved(old(s), s))]
+    #[ensures(errors_preserved(old(s), s))]
+    #[ensures(constants_preserved(old(s), s))]
+    pub unsafe fn from_raw(ptr: PytObjectPointer, s: &mut GpyGlobalState) -> Owned {
+        // `Clone` and `Drop` change the reference count without the state at hand
+        #[cfg(feature = "shadow-heap")]
+        {
+            s.shadow.forget(ptr);
+            s.refs_held -= 1;
+        }
+        Owned::wrap(ptr)
+    }
+
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(gpy_ref_held(1, result))]
+    #[ensures(result === old(self.ptr()))]
+    #[ensures(refs_held_changed_by(1, old(s), s))]
+    #[ensures(all_objects_preserved(old(s), s))]
+    #[ensures(errors_preserved(old(s), s))]
+    #[ensures(constants_preserved(old(s), s))]
+    pub fn into_raw(self, s: &mut GpyGlobalState) -> PytObjectPointer {
+        let ptr = self.ptr;
+        std::mem::forget(self);
+        #[cfg(feature = "shadow-heap")]
+        {
+            s.refs_held += 1;
+        }
+        ptr
+    }
+
+    // gives the reference back; that may free the object and run its finalizer
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[requires(self.ptr().gpy_get(s).lent == 0)]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(gpy_havoc(old(s), s))]
+    #[ensures(errors_preserved(old(s), s))]
+    #[ensures(constants_preserved(old(s), s))]
+    #[ensures(refs_held_preserved(old(s), s))]
+    pub fn release(self, s: &mut GpyGlobalState) {
+        let ptr = self.ptr;
+        std::mem::forget(self);
+        unsafe {
+            Py_DecRef(ptr);
+        }
+        #[cfg(feature = "shadow-heap")]
+        s.shadow_havoc();
+    }
+
+    #[pure]
+    pub fn ptr(&self) -> PytObjectPointer {
+        self.ptr
+    }
+
+    #[trusted]
+    #[ensures(result.ptr() === self.ptr())]
+    pub fn borrow<'a>(&'a self) -> Borrowed<'a> {
+        Borrowed { ptr: self.ptr, _owner: PhantomData }
+    }
+
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(constants_preserved(old(s), s))]
+    #[ensures(refs_held_preserved(old(s), s))]
+    #[ensures(match &result {
+        Some(o) => o.ptr().gpy_get(s).data === GpyObjectData::PyLong(GpyLongData::Known(v)) && errors_preserved(old(s), s),
+        None => s.error === GpyErrorState::Set(PytExceptionType::MemoryError),
+    })]
+    pub fn from_long(v: libc::c_long, s: &mut GpyGlobalState) -> Option<Owned> {
+        unsafe {
+            let ptr = pytlong_fromlong(v, s);
+            if ptr.is_null() {
+                None
+            } else {
+                Some(Owned::from_raw(ptr, s))
+            }
+        }
+    }
+
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(constants_preserved(old(s), s))]
+    #[ensures(refs_held_preserved(old(s), s))]
+    #[ensures(match &result {
+        Some(o) => o.ptr().gpy_get(s).data === GpyObjectData::PyBytes(v.len() as pyt_ssize_t) && errors_preserved(old(s), s),
+        None => s.error === GpyErrorState::Set(PytExceptionType::MemoryError),
+    })]
+    pub fn from_bytes(v: &[u8], s: &mut GpyGlobalState) -> Option<Owned> {
+        unsafe {
+            let ptr = pytbytes_fromslice(v, s);
+            if ptr.is_null() {
+                None
+            } else {
+                Some(Owned::from_raw(ptr, s))
+            }
+        }
+    }
+
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[requires(len >= 0)]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(constants_preserved(old(s), s))]
+    #[ensures(refs_held_preserved(old(s), s))]
+    #[ensures(match &result {
+        Some(o) => o.ptr().gpy_get(s).data.is_list() && o.ptr().gpy_get(s).data.seq_len() == len && errors_preserved(old(s), s),
+        None => s.error === GpyErrorState::Set(PytExceptionType::MemoryError),
+    })]
+    pub fn new_list(len: pyt_ssize_t, s: &mut GpyGlobalState) -> Option<Owned> {
+        unsafe {
+            let ptr = pytlist_new(len, s);
+            if ptr.is_null() {
+                None
+            } else {
+                Some(Owned::from_raw(ptr, s))
+            }
+        }
+    }
+
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(result.ptr() === s.constants.py_none)]
+    #[ensures(constants_preserved(old(s), s))]
+    #[ensures(errors_preserved(old(s), s))]
+    #[ensures(refs_held_preserved(old(s), s))]
+    pub fn none(s: &mut GpyGlobalState) -> Owned {
+        unsafe {
+            let ptr = pytnone_get(s);
+            Owned::from_raw(ptr, s)
+        }
+    }
+
+    // like `PySequence_GetItem`, this returns a new reference, so the item stays valid no matter
+    // what happens to the list afterwards
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[requires(self.ptr().gpy_get(s).data.is_list())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(constants_preserved(old(s), s))]
+    #[ensures(refs_held_preserved(old(s), s))]
+    #[ensures(0 <= index && index < self.ptr().gpy_get(old(s)).data.seq_len() && !self.ptr().gpy_get(old(s)).data.seq_item(index).is_null() ==>
+        match &result {
+            Some(o) => o.ptr() === self.ptr().gpy_get(old(s)).data.seq_item(index),
+            None => false,
+        })]
+    #[ensures(!(0 <= index && index < self.ptr().gpy_get(old(s)).data.seq_len()) ==> result.is_none() && s.error === GpyErrorState::Set(PytExceptionType::IndexError))]
+    #[ensures(result.is_some() ==> errors_preserved(old(s), s))]
+    pub fn list_get(&self, index: pyt_ssize_t, s: &mut GpyGlobalState) -> Option<Owned> {
+        unsafe {
+            let item = PyList_GetItem(self.ptr, index);
+            if item.is_null() {
+                None
+            } else {
+                Py_IncRef(item);
+                Some(Owned::wrap(item))
+            }
+        }
+    }
+
+    // takes over the reference held by `item`
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[requires(self.ptr().gpy_get(s).data.is_list())]
+    #[requires(self.ptr().gpy_get(s).lent == 0)]
+    #[requires(self.ptr() !== item.ptr())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(constants_preserved(old(s), s))]
+    #[ensures(refs_held_preserved(old(s), s))]
+    #[ensures(result <==> 0 <= index && index < self.ptr().gpy_get(old(s)).data.seq_len())]
+    #[ensures(result ==> seq_items_preserved_except(self.ptr(), index, old(s), s) && self.ptr().gpy_get(s).data.seq_item(index) === old(item.ptr()))]
+    #[ensures(result ==> errors_preserved(old(s), s))]
+    #[ensures(!result ==> s.error === GpyErrorState::Set(PytExceptionType::IndexError))]
+    pub fn list_set(&mut self, index: pyt_ssize_t, item: Owned, s: &mut GpyGlobalState) -> bool {
+        unsafe {
+            let item = item.into_raw(s);
+            pytlist_setitem(self.ptr, index, item, s) == 0
+        }
+    }
+
+    // `None` with the error left set if `self` isn't iterable
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[requires(gpy_no_borrows_active(s))]
+    #[requires(!s.error.is_set())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(self.ptr().gpy_get(s).ref_count >= 1)]
+    #[ensures(gpy_havoc(old(s), s))]
+    #[ensures(constants_preserved(old(s), s))]
+    #[ensures(refs_held_preserved(old(s), s))]
+    #[ensures(result.is_some() ==> !s.error.is_set())]
+    #[ensures(result.is_none() ==> s.error.is_set())]
+    pub fn iter(&self, s: &mut GpyGlobalState) -> Option<Iter> {
+        unsafe {
+            let iter = PyObject_GetIter(self.ptr);
+            #[cfg(feature = "shadow-heap")]
+            {
+                s.shadow_havoc();
+                s.shadow_error();
+            }
+            if iter.is_null() {
+                None
+            } else {
+                Some(Iter { iter: Owned::wrap(iter) })
+            }
+        }
+    }
+}
+
+// walks a Python iterator. It isn't an `Iterator`: each step runs Python code (`__next__`), which
+// needs the state and a spec of its own
+pub struct Iter {
+    iter: Owned,
+}
+
+impl Iter {
+    // a new reference to the next item; `None` once the iterator is exhausted, or with the error
+    // left set if it raised
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[requires(gpy_no_borrows_active(s))]
+    #[requires(!s.error.is_set())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(gpy_havoc(old(s), s))]
+    #[ensures(constants_preserved(old(s), s))]
+    #[ensures(refs_held_preserved(old(s), s))]
+    #[ensures(match &result {
+        Some(o) => o.ptr().gpy_get(s).ref_count >= 1 && !s.error.is_set(),
+        None => true,
+    })]
+    pub fn next(&mut self, s: &mut GpyGlobalState) -> Option<Owned> {
+        unsafe {
+            let item = PyIter_Next(self.iter.ptr);
+            #[cfg(feature = "shadow-heap")]
+            {
+                s.shadow_havoc();
+                s.shadow_error();
+            }
+            if item.is_null() {
+                None
+            } else {
+                Some(Owned::wrap(item))
+            }
+        }
+    }
+}
+
+impl Clone for Owned {
+    #[trusted]
+    #[ensures(result.ptr() === self.ptr())]
+    fn clone(&self) -> Owned {
+        if INTERPRETER.load(Ordering::SeqCst) == self.interpreter {
+            unsafe {
+                let gstate = PyGILState_Ensure();
+                Py_IncRef(self.ptr);
+                PyGILState_Release(gstate);
+            }
+        }
+        Owned { ptr: self.ptr, interpreter: self.interpreter }
+    }
+}
+
+// once the interpreter the object lived in is finalized, the object is gone already, so there is
+// nothing left to give back (even if another interpreter was started since); `Drop` has no way of
+// asking for `gpy_gil_held`, so it takes the GIL itself (which is fine if held already)
+impl Drop for Owned {
+    #[trusted]
+    fn drop(&mut self) {
+        if INTERPRETER.load(Ordering::SeqCst) == self.interpreter {
+            unsafe {
+                let gstate = PyGILState_Ensure();
+                Py_DecRef(self.ptr);
+                PyGILState_Release(gstate);
+            }
+        }
+    }
+}
+
+impl<'a> Borrowed<'a> {
+    #[pure]
+    pub fn ptr(&self) -> PytObjectPointer {
+        self.ptr
+    }
+
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(result.ptr() === self.ptr())]
+    #[ensures(ref_count_changed_by(self.ptr(), 1, old(s), s))]
+    #[ensures(self.ptr().gpy_get(s).data === self.ptr().gpy_get(old(s)).data && self.ptr().gpy_get(s).lent == self.ptr().gpy_get(old(s)).lent)]
+    #[ensures(all_other_preserved(self.ptr(), old(s), s))]
+    #[ensures(errors_preserved(old(s), s))]
+    #[ensures(constants_preserved(old(s), s))]
+    #[ensures(refs_held_preserved(old(s), s))]
+    pub fn to_owned(&self, s: &mut GpyGlobalState) -> Owned {
+        unsafe {
+            Py_IncRef(self.ptr);
+        }
+        Owned::wrap(self.ptr)
+    }
+
+    #[trusted]
+    #[pure]
+    #[ensures(result <==> self.ptr() === s.constants.py_none)]
+    pub fn is_none(&self, s: &GpyGlobalState) -> bool {
+        unsafe { My_IsNone(self.ptr) != 0 }
+    }
+
+    #[trusted]
+    #[pure]
+    #[ensures(result <==> self.ptr().gpy_get(s).data.is_long())]
+    pub fn is_long(&self, s: &GpyGlobalState) -> bool {
+        unsafe { MyLong_CheckExact(self.ptr) != 0 }
+    }
+
+    #[trusted]
+    #[pure]
+    #[ensures(result <==> self.ptr().gpy_get(s).data.is_list())]
+    pub fn is_list(&self, s: &GpyGlobalState) -> bool {
+        unsafe { MyList_CheckExact(self.ptr) != 0 }
+    }
+
+    // `None` on overflow, with the OverflowError left set
+    #[trusted]
+    #[requires(gpy_gil_held(1))]
+    #[requires(self.ptr().gpy_get(s).data.is_long())]
+    #[requires(!s.error.is_set())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(all_objects_preserved(old(s), s))]
+    #[ensures(constants_preserved(old(s), s))]
+    #[ensures(refs_held_preserved(old(s), s))]
+    #[ensures(self.ptr().gpy_get(s).data.is_known_long() ==> result === Some(self.ptr().gpy_get(s).data.long_value()))]
+    #[ensures(result.is_none() <==> s.error === GpyErrorState::Set(PytExceptionType::OverflowError))]
+    #[ensures(result.is_some() ==> !s.error.is_set())]
+    pub fn as_long(&self, s: &mut GpyGlobalState) -> Option<libc::c_long> {
+        unsafe {
+            let v = PyLong_AsLong(self.ptr);
+            if v == -1 && !PyErr_Occurred().is_null() {
+                None
+            } else {
+                Some(v)
+            }
+        }
+    }
+
+    #[trusted]
+    #[pure]
+    #[requires(self.ptr().gpy_get(s).data.is_list())]
+    #[ensures(result == self.ptr().gpy_get(s).data.seq_len())]
+    pub fn list_len(&self, s: &GpyGlobalState) -> pyt_ssize_t {
+        unsafe { PyList_Size(self.ptr) }
+    }
+
+    // bytes objects are immutable, so the contents stay valid for as long as the handle does; None
+    // if the object isn't bytes
+    #[requires(gpy_gil_held(1))]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(result.is_some() <==> self.ptr().gpy_get(s).data.is_bytes())]
+    #[ensures(match result { Some(v) => v.len() == self.ptr().gpy_get(s).data.bytes_len() as usize, None => true })]
+    pub fn as_bytes(&self, s: &GpyGlobalState) -> Option<&'a [u8]> {
+        unsafe { pytbytes_asslice(*self, s) }
+    }
+}
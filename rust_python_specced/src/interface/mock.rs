@@ -0,0 +1,1388 @@
+// A stand-in for libpython (the `mock-python` feature): everything the extern blocks declare,
+// including the `My*` shims from auxbinds.c and api.table, implemented in Rust, so the wrappers
+// run on machines without Python (build.rs then neither compiles the shims nor links anything).
+//
+// Objects live in a table instead of in memory. The object at index `i` has the address
+// `MOCK_BASE + i * MOCK_ALIGN`; indices are handed out in order and never reused, so every run
+// sees the same addresses and using a released object panics instead of reading garbage.
+// Reference counting is real: an object is released when its count drops to zero, and so is what
+// it owns (without a cycle collector, cycles such as a module and its functions leak until
+// `Py_Finalize`). As in CPython, the ints from -5 to 256 and the empty and single-character bytes
+// and strs are singletons, which constructors hand out again instead of making new objects.
+//
+// There is no interpreter, so running or importing Python code always fails. Only what the
+// wrappers need is there: ints are limited to `i128`, arithmetic works on ints and floats only,
+// and exceptions match their exact type only.
+
+use std::ffi::CStr;
+use std::sync::{Condvar, Mutex};
+use std::thread::{self, ThreadId};
+
+use super::extension::{PytMethodDef, METH_NOARGS, METH_O, METH_VARARGS};
+use super::*;
+
+type PytDestructor = unsafe extern "C" fn(PytObjectPointer);
+type PytGetAttroFunc = unsafe extern "C" fn(PytObjectPointer, PytObjectPointer) -> PytObjectPointer;
+type PytReprFunc = unsafe extern "C" fn(PytObjectPointer) -> PytObjectPointer;
+type PytBinaryFunc = unsafe extern "C" fn(PytObjectPointer, PytObjectPointer) -> PytObjectPointer;
+
+const MOCK_BASE: usize = 0x10000;
+const MOCK_ALIGN: usize = 0x40;
+
+const fn mock_addr(index: usize) -> usize {
+    MOCK_BASE + index * MOCK_ALIGN
+}
+
+// the objects `Py_Initialize` creates, by index
+const NONE: usize = mock_addr(0);
+const FALSE: usize = mock_addr(1);
+const TRUE: usize = mock_addr(2);
+const NOT_IMPLEMENTED: usize = mock_addr(3);
+const TYPE_TYPE: usize = mock_addr(4);
+const NONE_TYPE: usize = mock_addr(5);
+const NOT_IMPLEMENTED_TYPE: usize = mock_addr(6);
+const BOOL_TYPE: usize = mock_addr(7);
+const LONG_TYPE: usize = mock_addr(8);
+const FLOAT_TYPE: usize = mock_addr(9);
+const UNICODE_TYPE: usize = mock_addr(10);
+const BYTES_TYPE: usize = mock_addr(11);
+const LIST_TYPE: usize = mock_addr(12);
+const TUPLE_TYPE: usize = mock_addr(13);
+const DICT_TYPE: usize = mock_addr(14);
+const MODULE_TYPE: usize = mock_addr(15);
+const FUNCTION_TYPE: usize = mock_addr(16);
+const ITERATOR_TYPE: usize = mock_addr(17);
+const OVERFLOW_ERROR: usize = mock_addr(18);
+const ZERO_DIVISION_ERROR: usize = mock_addr(19);
+const MEMORY_ERROR: usize = mock_addr(20);
+const TYPE_ERROR: usize = mock_addr(21);
+const VALUE_ERROR: usize = mock_addr(22);
+const INDEX_ERROR: usize = mock_addr(23);
+const KEY_ERROR: usize = mock_addr(24);
+const ATTRIBUTE_ERROR: usize = mock_addr(25);
+const SYSTEM_ERROR: usize = mock_addr(26);
+const MODULE_NOT_FOUND_ERROR: usize = mock_addr(27);
+const BUFFER_ERROR: usize = mock_addr(28);
+
+// the type objects among them, with their `tp_name`
+const STATIC_TYPES: [(usize, &[u8]); 25] = [
+    (TYPE_TYPE, b"type\0"),
+    (NONE_TYPE, b"NoneType\0"),
+    (NOT_IMPLEMENTED_TYPE, b"NotImplementedType\0"),
+    (BOOL_TYPE, b"bool\0"),
+    (LONG_TYPE, b"int\0"),
+    (FLOAT_TYPE, b"float\0"),
+    (UNICODE_TYPE, b"str\0"),
+    (BYTES_TYPE, b"bytes\0"),
+    (LIST_TYPE, b"list\0"),
+    (TUPLE_TYPE, b"tuple\0"),
+    (DICT_TYPE, b"dict\0"),
+    (MODULE_TYPE, b"module\0"),
+    (FUNCTION_TYPE, b"builtin_function_or_method\0"),
+    (ITERATOR_TYPE, b"iterator\0"),
+    (OVERFLOW_ERROR, b"OverflowError\0"),
+    (ZERO_DIVISION_ERROR, b"ZeroDivisionError\0"),
+    (MEMORY_ERROR, b"MemoryError\0"),
+    (TYPE_ERROR, b"TypeError\0"),
+    (VALUE_ERROR, b"ValueError\0"),
+    (INDEX_ERROR, b"IndexError\0"),
+    (KEY_ERROR, b"KeyError\0"),
+    (ATTRIBUTE_ERROR, b"AttributeError\0"),
+    (SYSTEM_ERROR, b"SystemError\0"),
+    (MODULE_NOT_FOUND_ERROR, b"ModuleNotFoundError\0"),
+    (BUFFER_ERROR, b"BufferError\0"),
+];
+
+const STATIC_OBJECTS: usize = 29;
+
+#[no_mangle]
+pub static PyExc_OverflowError: PytObjectPointer = PytObjectPointer { _private: OVERFLOW_ERROR };
+#[no_mangle]
+pub static PyExc_ZeroDivisionError: PytObjectPointer = PytObjectPointer { _private: ZERO_DIVISION_ERROR };
+#[no_mangle]
+pub static PyExc_MemoryError: PytObjectPointer = PytObjectPointer { _private: MEMORY_ERROR };
+#[no_mangle]
+pub static PyExc_TypeError: PytObjectPointer = PytObjectPointer { _private: TYPE_ERROR };
+#[no_mangle]
+pub static PyExc_ValueError: PytObjectPointer = PytObjectPointer { _private: VALUE_ERROR };
+#[no_mangle]
+pub static PyExc_IndexError: PytObjectPointer = PytObjectPointer { _private: INDEX_ERROR };
+#[no_mangle]
+pub static PyExc_KeyError: PytObjectPointer = PytObjectPointer { _private: KEY_ERROR };
+#[no_mangle]
+pub static PyExc_AttributeError: PytObjectPointer = PytObjectPointer { _private: ATTRIBUTE_ERROR };
+#[no_mangle]
+pub static PyExc_SystemError: PytObjectPointer = PytObjectPointer { _private: SYSTEM_ERROR };
+#[no_mangle]
+pub static PyExc_BufferError: PytObjectPointer = PytObjectPointer { _private: BUFFER_ERROR };
+
+struct MockType {
+    name: std::ffi::CString, // a copy, like the one `PyType_FromSpec` makes
+    dealloc: Option<PytDestructor>,
+    getattro: Option<PytGetAttroFunc>,
+    nb_add: Option<PytBinaryFunc>,
+}
+
+enum MockData {
+    Constant, // None, the bools and NotImplemented (told apart by their address)
+    Long(i128),
+    Float(f64),
+    Unicode(std::ffi::CString),
+    Bytes(Box<[u8]>), // with a NUL byte after the contents, like `PyBytes_AsString` promises
+    List(Vec<usize>),
+    Tuple(Vec<usize>),
+    Dict,
+    Module { name: std::ffi::CString, attrs: Vec<(std::ffi::CString, usize)> },
+    Function { def: *const PytMethodDef, module: usize },
+    Iterator { seq: usize, index: pyt_ssize_t },
+    Type(MockType),
+    Instance(*mut libc::c_void),
+}
+
+struct MockObject {
+    ref_count: pyt_ssize_t,
+    typ: usize,
+    data: MockData,
+}
+
+struct MockHeap {
+    objects: Vec<Option<MockObject>>,
+    error: Option<(usize, usize, usize)>, // type, value and traceback of the pending exception
+    singletons: MockSingletons,
+}
+
+// the objects CPython creates once and hands out whenever one with the same value is asked for
+struct MockSingletons {
+    small_ints: Vec<usize>, // -5 to 256
+    bytes: Vec<usize>,      // b"" and then each single byte
+    unicode: Vec<usize>,    // "" and then each single character below U+0100
+}
+
+const SMALL_INT_MIN: i128 = -5;
+const SMALL_INT_MAX: i128 = 256;
+
+// the raw pointers are only ever used with the lock held, like the GIL would
+unsafe impl Send for MockHeap {}
+
+static MOCK_HEAP: Mutex<Option<MockHeap>> = Mutex::new(None);
+
+// the thread holding the GIL, if any
+static MOCK_GIL: Mutex<Option<ThreadId>> = Mutex::new(None);
+static MOCK_GIL_FREED: Condvar = Condvar::new();
+
+fn mock_take_gil() {
+    let mut holder = MOCK_GIL.lock().unwrap_or_else(|e| e.into_inner());
+    while holder.is_some() {
+        holder = MOCK_GIL_FREED.wait(holder).unwrap_or_else(|e| e.into_inner());
+    }
+    *holder = Some(thread::current().id());
+}
+
+fn mock_drop_gil() {
+    let mut holder = MOCK_GIL.lock().unwrap_or_else(|e| e.into_inner());
+    assert!(*holder == Some(thread::current().id()), "mock: this thread doesn't hold the GIL");
+    *holder = None;
+    MOCK_GIL_FREED.notify_one();
+}
+
+fn mock_holds_gil() -> bool {
+    *MOCK_GIL.lock().unwrap_or_else(|e| e.into_inner()) == Some(thread::current().id())
+}
+
+fn with_heap<R>(f: impl FnOnce(&mut MockHeap) -> R) -> R {
+    let mut heap = MOCK_HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    f(heap.as_mut().expect("mock: the interpreter isn't initialized"))
+}
+
+impl MockHeap {
+    fn new() -> MockHeap {
+        let singletons = MockSingletons { small_ints: Vec::new(), bytes: Vec::new(), unicode: Vec::new() };
+        let mut heap = MockHeap { objects: (0..STATIC_OBJECTS).map(|_| None).collect(), error: None, singletons };
+        let constants = [(NONE, NONE_TYPE), (FALSE, BOOL_TYPE), (TRUE, BOOL_TYPE), (NOT_IMPLEMENTED, NOT_IMPLEMENTED_TYPE)];
+        for (obj, typ) in constants {
+            heap.objects[(obj - MOCK_BASE) / MOCK_ALIGN] = Some(MockObject { ref_count: 1, typ, data: MockData::Constant });
+        }
+        for (obj, name) in STATIC_TYPES {
+            let typ = MockType { name: CStr::from_bytes_with_nul(name).unwrap().to_owned(), dealloc: None, getattro: None, nb_add: None };
+            heap.objects[(obj - MOCK_BASE) / MOCK_ALIGN] = Some(MockObject { ref_count: 1, typ: TYPE_TYPE, data: MockData::Type(typ) });
+        }
+        for v in SMALL_INT_MIN..=SMALL_INT_MAX {
+            let obj = heap.alloc(LONG_TYPE, MockData::Long(v));
+            heap.singletons.small_ints.push(obj);
+        }
+        let characters = (0..=255u8).map(|c| vec![c]);
+        for bytes in std::iter::once(Vec::new()).chain(characters.clone()) {
+            let obj = heap.alloc(BYTES_TYPE, MockData::Bytes([&bytes[..], &[0]].concat().into_boxed_slice()));
+            heap.singletons.bytes.push(obj);
+        }
+        for latin1 in std::iter::once(String::new()).chain((0..=255u8).map(|c| char::from(c).to_string())) {
+            let obj = heap.alloc(UNICODE_TYPE, MockData::Unicode(std::ffi::CString::new(latin1.replace('\0', "\\0")).unwrap()));
+            heap.singletons.unicode.push(obj);
+        }
+        heap
+    }
+
+    // a new reference to the singleton `obj`
+    fn singleton(&mut self, obj: usize) -> usize {
+        self.incref(obj);
+        obj
+    }
+
+    fn new_long(&mut self, v: i128) -> usize {
+        if (SMALL_INT_MIN..=SMALL_INT_MAX).contains(&v) {
+            return self.singleton(self.singletons.small_ints[(v - SMALL_INT_MIN) as usize]);
+        }
+        self.alloc(LONG_TYPE, MockData::Long(v))
+    }
+
+    fn new_bytes(&mut self, v: &[u8]) -> usize {
+        match v {
+            [] => self.singleton(self.singletons.bytes[0]),
+            &[c] => self.singleton(self.singletons.bytes[1 + c as usize]),
+            _ => self.alloc(BYTES_TYPE, MockData::Bytes([v, &[0]].concat().into_boxed_slice())),
+        }
+    }
+
+    #[track_caller]
+    fn get(&self, obj: usize) -> &MockObject {
+        assert!(obj != 0, "mock: NULL passed as an object");
+        let index = (obj.wrapping_sub(MOCK_BASE)) / MOCK_ALIGN;
+        assert!(obj >= MOCK_BASE && obj % MOCK_ALIGN == 0 && index < self.objects.len(), "mock: {:#x} isn't an object", obj);
+        match &self.objects[index] {
+            Some(o) => o,
+            None => panic!("mock: object {:#x} was released already", obj),
+        }
+    }
+
+    #[track_caller]
+    fn get_mut(&mut self, obj: usize) -> &mut MockObject {
+        self.get(obj);
+        self.objects[(obj - MOCK_BASE) / MOCK_ALIGN].as_mut().unwrap()
+    }
+
+    fn alloc(&mut self, typ: usize, data: MockData) -> usize {
+        self.objects.push(Some(MockObject { ref_count: 1, typ, data }));
+        mock_addr(self.objects.len() - 1)
+    }
+
+    fn incref(&mut self, obj: usize) {
+        self.get_mut(obj).ref_count += 1;
+    }
+
+    // instances whose count drops to zero stay in the table for their type's `tp_dealloc`, which
+    // has to run without the lock held (see `mock_release`)
+    fn decref(&mut self, obj: usize, pending: &mut Vec<usize>) {
+        let o = self.get_mut(obj);
+        o.ref_count -= 1;
+        if o.ref_count > 0 {
+            return;
+        }
+        if let MockData::Instance(_) = o.data {
+            pending.push(obj);
+            return;
+        }
+        let o = self.objects[(obj - MOCK_BASE) / MOCK_ALIGN].take().unwrap();
+        let owned: Vec<usize> = match o.data {
+            MockData::List(items) | MockData::Tuple(items) => items,
+            MockData::Module { attrs, .. } => attrs.into_iter().map(|(_, value)| value).collect(),
+            MockData::Function { module, .. } => vec![module],
+            MockData::Iterator { seq, .. } => vec![seq],
+            _ => Vec::new(),
+        };
+        for item in owned.into_iter().filter(|&item| item != 0) {
+            self.decref(item, pending);
+        }
+    }
+
+    fn typ(&self, obj: usize) -> &MockType {
+        match &self.get(self.get(obj).typ).data {
+            MockData::Type(t) => t,
+            _ => unreachable!(),
+        }
+    }
+
+    fn type_name(&self, obj: usize) -> String {
+        self.typ(obj).name.to_string_lossy().into_owned()
+    }
+
+    fn attr_error(&self, obj: usize, attr: &CStr) -> String {
+        match &self.get(obj).data {
+            MockData::Module { name, .. } => format!("module '{}' has no attribute '{}'", name.to_string_lossy(), attr.to_string_lossy()),
+            _ => format!("'{}' object has no attribute '{}'", self.type_name(obj), attr.to_string_lossy()),
+        }
+    }
+
+    fn new_unicode(&mut self, s: &str) -> usize {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (None, _) => return self.singleton(self.singletons.unicode[0]),
+            (Some(c), None) if (c as u32) < 0x100 => return self.singleton(self.singletons.unicode[1 + c as usize]),
+            _ => {}
+        }
+        let s = std::ffi::CString::new(s.replace('\0', "\\0")).unwrap();
+        self.alloc(UNICODE_TYPE, MockData::Unicode(s))
+    }
+
+    fn new_bool(&mut self, v: bool) -> usize {
+        let obj = if v { TRUE } else { FALSE };
+        self.incref(obj);
+        obj
+    }
+
+    // sets the error indicator; returns what it replaces, to be released without the lock held
+    fn raise(&mut self, exc: usize, message: &str) -> Option<(usize, usize, usize)> {
+        self.incref(exc);
+        let value = self.new_unicode(message);
+        self.error.replace((exc, value, 0))
+    }
+
+    // an int operand (bools are ints) as Python sees it
+    fn as_int(&self, obj: usize) -> Option<i128> {
+        match (obj, &self.get(obj).data) {
+            (FALSE, _) => Some(0),
+            (TRUE, _) => Some(1),
+            (_, MockData::Long(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    // a number operand converted to a float
+    fn as_float(&self, obj: usize) -> Option<f64> {
+        match &self.get(obj).data {
+            MockData::Float(v) => Some(*v),
+            _ => self.as_int(obj).map(|v| v as f64),
+        }
+    }
+}
+
+// gives up the references to `objs` (NULLs are skipped), running the `tp_dealloc` of instances
+// that are released
+fn mock_release(objs: &[usize]) {
+    let mut pending = Vec::new();
+    with_heap(|h| {
+        for &obj in objs.iter().filter(|&&obj| obj != 0) {
+            h.decref(obj, &mut pending);
+        }
+    });
+    for obj in pending {
+        let dealloc = with_heap(|h| h.typ(obj).dealloc).expect("mock: instance of a type without tp_dealloc");
+        unsafe { dealloc(PytObjectPointer { _private: obj }) }
+    }
+}
+
+fn mock_release_error(error: Option<(usize, usize, usize)>) {
+    if let Some((typ, value, traceback)) = error {
+        mock_release(&[typ, value, traceback]);
+    }
+}
+
+// raises `exc` and returns NULL, the usual way of failing
+fn mock_fail(exc: usize, message: &str) -> PytObjectPointer {
+    mock_release_error(with_heap(|h| h.raise(exc, message)));
+    PytObjectPointer { _private: 0 }
+}
+
+fn mock_ptr(obj: usize) -> PytObjectPointer {
+    PytObjectPointer { _private: obj }
+}
+
+// whether `obj` hasn't been released yet
+#[cfg(test)]
+pub(super) fn mock_is_alive(obj: PytObjectPointer) -> bool {
+    with_heap(|h| h.objects.get(obj._private.wrapping_sub(MOCK_BASE) / MOCK_ALIGN).is_some_and(Option::is_some))
+}
+
+// the calling thread gets the GIL
+#[no_mangle]
+pub extern "C" fn Py_Initialize() {
+    let mut heap = MOCK_HEAP.lock().unwrap_or_else(|e| e.into_inner());
+    if heap.is_none() {
+        *heap = Some(MockHeap::new());
+        mock_take_gil();
+    }
+}
+
+// whatever is still alive is dropped without running any `tp_dealloc`, as CPython may do too
+#[no_mangle]
+pub extern "C" fn Py_Finalize() {
+    *MOCK_HEAP.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    *MOCK_GIL.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    MOCK_GIL_FREED.notify_one();
+}
+
+#[no_mangle]
+pub extern "C" fn Py_IncRef(obj: PytObjectPointer) {
+    if !obj.is_null() {
+        with_heap(|h| h.incref(obj._private));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn Py_DecRef(obj: PytObjectPointer) {
+    mock_release(&[obj._private]);
+}
+
+#[no_mangle]
+pub extern "C" fn My_REFCNT(obj: PytObjectPointer) -> pyt_ssize_t {
+    with_heap(|h| h.get(obj._private).ref_count)
+}
+
+// like 3.11, which has no immortal objects
+#[no_mangle]
+pub extern "C" fn My_IsImmortal(_obj: PytObjectPointer) -> libc::c_int {
+    0
+}
+
+// a thread state is just the error indicator, which is per thread in CPython: it is put away while
+// the thread doesn't hold the GIL
+#[no_mangle]
+pub extern "C" fn PyEval_SaveThread() -> *mut libc::c_void {
+    let error = with_heap(|h| h.error.take());
+    mock_drop_gil();
+    Box::into_raw(Box::new(error)) as *mut libc::c_void
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyEval_RestoreThread(tstate: *mut libc::c_void) {
+    let error = *Box::from_raw(tstate as *mut Option<(usize, usize, usize)>);
+    mock_take_gil();
+    with_heap(|h| h.error = error);
+}
+
+// returns `PyGILState_LOCKED` (0) if the thread held the GIL already, else `PyGILState_UNLOCKED` (1)
+#[no_mangle]
+pub extern "C" fn PyGILState_Ensure() -> libc::c_int {
+    if mock_holds_gil() {
+        return 0;
+    }
+    mock_take_gil();
+    1
+}
+
+// the thread state made by `PyGILState_Ensure` is deleted along with its error indicator
+#[no_mangle]
+pub extern "C" fn PyGILState_Release(state: libc::c_int) {
+    if state == 1 {
+        mock_release_error(with_heap(|h| h.error.take()));
+        mock_drop_gil();
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn PyGILState_Check() -> libc::c_int {
+    mock_holds_gil() as libc::c_int
+}
+
+#[no_mangle]
+pub extern "C" fn PyLong_AsLong(obj: PytObjectPointer) -> libc::c_long {
+    let (v, name) = with_heap(|h| (h.as_int(obj._private), h.type_name(obj._private)));
+    match v.map(libc::c_long::try_from) {
+        Some(Ok(v)) => v,
+        Some(Err(_)) => {
+            mock_fail(OVERFLOW_ERROR, "Python int too large to convert to C long");
+            -1
+        }
+        None => {
+            mock_fail(TYPE_ERROR, &format!("'{}' object cannot be interpreted as an integer", name));
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn PyLong_FromLong(v: libc::c_long) -> PytObjectPointer {
+    mock_ptr(with_heap(|h| h.new_long(v as i128)))
+}
+
+fn mock_is_type(obj: PytObjectPointer, typ: usize) -> libc::c_int {
+    with_heap(|h| (h.get(obj._private).typ == typ) as libc::c_int)
+}
+
+#[no_mangle]
+pub extern "C" fn MyLong_CheckExact(obj: PytObjectPointer) -> libc::c_int {
+    mock_is_type(obj, LONG_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn PyDict_New() -> PytObjectPointer {
+    mock_ptr(with_heap(|h| h.alloc(DICT_TYPE, MockData::Dict)))
+}
+
+#[no_mangle]
+pub extern "C" fn MyDict_CheckExact(obj: PytObjectPointer) -> libc::c_int {
+    mock_is_type(obj, DICT_TYPE)
+}
+
+fn mock_items(h: &mut MockHeap, seq: usize) -> Option<&mut Vec<usize>> {
+    match &mut h.get_mut(seq).data {
+        MockData::List(items) | MockData::Tuple(items) => Some(items),
+        _ => None,
+    }
+}
+
+fn mock_seq_new(typ: usize, len: pyt_ssize_t) -> PytObjectPointer {
+    if len < 0 {
+        return mock_fail(SYSTEM_ERROR, "bad argument to internal function");
+    }
+    let items = vec![0; len as usize];
+    mock_ptr(with_heap(|h| h.alloc(typ, if typ == LIST_TYPE { MockData::List(items) } else { MockData::Tuple(items) })))
+}
+
+fn mock_seq_size(seq: PytObjectPointer, typ: usize) -> pyt_ssize_t {
+    if mock_is_type(seq, typ) == 0 {
+        mock_fail(SYSTEM_ERROR, "bad argument to internal function");
+        return -1;
+    }
+    with_heap(|h| mock_items(h, seq._private).unwrap().len() as pyt_ssize_t)
+}
+
+// the item is borrowed
+fn mock_seq_getitem(seq: PytObjectPointer, typ: usize, index: pyt_ssize_t) -> PytObjectPointer {
+    if mock_is_type(seq, typ) == 0 {
+        return mock_fail(SYSTEM_ERROR, "bad argument to internal function");
+    }
+    let item = with_heap(|h| mock_items(h, seq._private).unwrap().get(index as usize).copied());
+    match item {
+        Some(item) if index >= 0 => mock_ptr(item),
+        _ => mock_fail(INDEX_ERROR, &format!("{} index out of range", if typ == LIST_TYPE { "list" } else { "tuple" })),
+    }
+}
+
+// steals `item` even if it fails; tuples can only be filled in while nothing else refers to them
+fn mock_seq_setitem(seq: PytObjectPointer, typ: usize, index: pyt_ssize_t, item: PytObjectPointer) -> libc::c_int {
+    let shared = with_heap(|h| h.get(seq._private).typ == TUPLE_TYPE && h.get(seq._private).ref_count != 1);
+    if mock_is_type(seq, typ) == 0 || shared {
+        mock_release(&[item._private]);
+        mock_fail(SYSTEM_ERROR, "bad argument to internal function");
+        return -1;
+    }
+    let replaced = with_heap(|h| match mock_items(h, seq._private).unwrap().get_mut(index as usize) {
+        Some(slot) if index >= 0 => Some(std::mem::replace(slot, item._private)),
+        _ => None,
+    });
+    match replaced {
+        Some(old) => {
+            mock_release(&[old]);
+            0
+        }
+        None => {
+            mock_release(&[item._private]);
+            mock_fail(INDEX_ERROR, &format!("{} assignment index out of range", if typ == LIST_TYPE { "list" } else { "tuple" }));
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn PyList_New(len: pyt_ssize_t) -> PytObjectPointer {
+    mock_seq_new(LIST_TYPE, len)
+}
+
+#[no_mangle]
+pub extern "C" fn PyList_Size(list: PytObjectPointer) -> pyt_ssize_t {
+    mock_seq_size(list, LIST_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn PyList_GetItem(list: PytObjectPointer, index: pyt_ssize_t) -> PytObjectPointer {
+    mock_seq_getitem(list, LIST_TYPE, index)
+}
+
+#[no_mangle]
+pub extern "C" fn PyList_SetItem(list: PytObjectPointer, index: pyt_ssize_t, item: PytObjectPointer) -> libc::c_int {
+    mock_seq_setitem(list, LIST_TYPE, index, item)
+}
+
+#[no_mangle]
+pub extern "C" fn MyList_CheckExact(obj: PytObjectPointer) -> libc::c_int {
+    mock_is_type(obj, LIST_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn PyTuple_New(len: pyt_ssize_t) -> PytObjectPointer {
+    mock_seq_new(TUPLE_TYPE, len)
+}
+
+#[no_mangle]
+pub extern "C" fn PyTuple_Size(tuple: PytObjectPointer) -> pyt_ssize_t {
+    mock_seq_size(tuple, TUPLE_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn PyTuple_GetItem(tuple: PytObjectPointer, index: pyt_ssize_t) -> PytObjectPointer {
+    mock_seq_getitem(tuple, TUPLE_TYPE, index)
+}
+
+#[no_mangle]
+pub extern "C" fn PyTuple_SetItem(tuple: PytObjectPointer, index: pyt_ssize_t, item: PytObjectPointer) -> libc::c_int {
+    mock_seq_setitem(tuple, TUPLE_TYPE, index, item)
+}
+
+#[no_mangle]
+pub extern "C" fn MyTuple_CheckExact(obj: PytObjectPointer) -> libc::c_int {
+    mock_is_type(obj, TUPLE_TYPE)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyUnicode_FromString(u: *const libc::c_char) -> PytObjectPointer {
+    match CStr::from_ptr(u).to_str() {
+        Ok(s) => mock_ptr(with_heap(|h| h.new_unicode(s))),
+        Err(_) => mock_fail(VALUE_ERROR, "'utf-8' codec can't decode the string"),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn MyUnicode_CheckExact(obj: PytObjectPointer) -> libc::c_int {
+    mock_is_type(obj, UNICODE_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn PyFloat_FromDouble(v: f64) -> PytObjectPointer {
+    mock_ptr(with_heap(|h| h.alloc(FLOAT_TYPE, MockData::Float(v))))
+}
+
+#[no_mangle]
+pub extern "C" fn PyFloat_AsDouble(obj: PytObjectPointer) -> f64 {
+    let (v, name) = with_heap(|h| (h.as_float(obj._private), h.type_name(obj._private)));
+    v.unwrap_or_else(|| {
+        mock_fail(TYPE_ERROR, &format!("must be real number, not {}", name));
+        -1.0
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn MyFloat_CheckExact(obj: PytObjectPointer) -> libc::c_int {
+    mock_is_type(obj, FLOAT_TYPE)
+}
+
+// NULL `v` gives `len` zero bytes
+#[no_mangle]
+pub unsafe extern "C" fn PyBytes_FromStringAndSize(v: *const libc::c_char, len: pyt_ssize_t) -> PytObjectPointer {
+    if len < 0 {
+        return mock_fail(SYSTEM_ERROR, "Negative size passed to PyBytes_FromStringAndSize");
+    }
+    // with no contents to copy, the object isn't shared (the caller fills it in), unless it is empty
+    if len == 0 {
+        return mock_ptr(with_heap(|h| h.new_bytes(&[])));
+    }
+    if v.is_null() {
+        return mock_ptr(with_heap(|h| h.alloc(BYTES_TYPE, MockData::Bytes(vec![0u8; len as usize + 1].into_boxed_slice()))));
+    }
+    mock_ptr(with_heap(|h| h.new_bytes(std::slice::from_raw_parts(v as *const u8, len as usize))))
+}
+
+#[no_mangle]
+pub extern "C" fn PyBytes_AsString(obj: PytObjectPointer) -> *mut libc::c_char {
+    let bytes = with_heap(|h| match &mut h.get_mut(obj._private).data {
+        MockData::Bytes(bytes) => Some(bytes.as_mut_ptr() as *mut libc::c_char),
+        _ => None,
+    });
+    bytes.unwrap_or_else(|| {
+        mock_fail(TYPE_ERROR, "expected bytes");
+        std::ptr::null_mut()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn PyBytes_Size(obj: PytObjectPointer) -> pyt_ssize_t {
+    let len = with_heap(|h| match &h.get(obj._private).data {
+        MockData::Bytes(bytes) => Some(bytes.len() as pyt_ssize_t - 1),
+        _ => None,
+    });
+    len.unwrap_or_else(|| {
+        mock_fail(TYPE_ERROR, "expected bytes");
+        -1
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn MyBytes_CheckExact(obj: PytObjectPointer) -> libc::c_int {
+    mock_is_type(obj, BYTES_TYPE)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyModule_New(name: *const libc::c_char) -> PytObjectPointer {
+    let name = CStr::from_ptr(name).to_owned();
+    mock_ptr(with_heap(|h| h.alloc(MODULE_TYPE, MockData::Module { name, attrs: Vec::new() })))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyImport_ImportModule(name: *const libc::c_char) -> PytObjectPointer {
+    mock_fail(MODULE_NOT_FOUND_ERROR, &format!("No module named '{}' (the mock can't import modules)", CStr::from_ptr(name).to_string_lossy()))
+}
+
+// like an exception escaping the command: reported on stderr and cleared
+#[no_mangle]
+pub unsafe extern "C" fn PyRun_SimpleString(command: *const libc::c_char) -> libc::c_int {
+    eprintln!("mock: can't run Python code: {}", CStr::from_ptr(command).to_string_lossy());
+    -1
+}
+
+// sets (or with NULL `value`, deletes) an attribute of a module; takes over `value`
+fn mock_module_setattr(module: usize, name: &CStr, value: usize) -> libc::c_int {
+    let missing = with_heap(|h| h.attr_error(module, name));
+    let old = with_heap(|h| match &mut h.get_mut(module).data {
+        MockData::Module { attrs, .. } => {
+            let index = attrs.iter().position(|(n, _)| n.as_c_str() == name);
+            Ok(match (index, value) {
+                (Some(i), 0) => Some(attrs.remove(i).1),
+                (Some(i), _) => Some(std::mem::replace(&mut attrs[i].1, value)),
+                (None, 0) => None,
+                (None, _) => {
+                    attrs.push((name.to_owned(), value));
+                    Some(0)
+                }
+            })
+        }
+        _ => Err(()),
+    });
+    match old {
+        Ok(Some(old)) => {
+            mock_release(&[old]);
+            0
+        }
+        Ok(None) => {
+            mock_fail(ATTRIBUTE_ERROR, &missing);
+            -1
+        }
+        Err(()) => {
+            mock_release(&[value]);
+            mock_fail(TYPE_ERROR, "the object isn't a module");
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyModule_AddObjectRef(module: PytObjectPointer, name: *const libc::c_char, value: PytObjectPointer) -> libc::c_int {
+    if value.is_null() {
+        mock_fail(SYSTEM_ERROR, "PyModule_AddObjectRef() must be called with an exception raised if value is NULL");
+        return -1;
+    }
+    Py_IncRef(value);
+    mock_module_setattr(module._private, CStr::from_ptr(name), value._private)
+}
+
+// only takes over `value` if it succeeds
+#[no_mangle]
+pub unsafe extern "C" fn PyModule_AddObject(module: PytObjectPointer, name: *const libc::c_char, value: PytObjectPointer) -> libc::c_int {
+    let result = PyModule_AddObjectRef(module, name, value);
+    if result == 0 {
+        Py_DecRef(value);
+    }
+    result
+}
+
+#[no_mangle]
+pub extern "C" fn MyNone_GetNoIncRef() -> PytObjectPointer {
+    mock_ptr(NONE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyBool_GetTrueNoIncRef() -> PytObjectPointer {
+    mock_ptr(TRUE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyBool_GetFalseNoIncRef() -> PytObjectPointer {
+    mock_ptr(FALSE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyNotImplemented_GetNoIncRef() -> PytObjectPointer {
+    mock_ptr(NOT_IMPLEMENTED)
+}
+
+#[no_mangle]
+pub extern "C" fn MyBool_Check(obj: PytObjectPointer) -> libc::c_int {
+    mock_is_type(obj, BOOL_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn My_IsNone(obj: PytObjectPointer) -> libc::c_int {
+    (obj._private == NONE) as libc::c_int
+}
+
+#[no_mangle]
+pub extern "C" fn My_IsTrue(obj: PytObjectPointer) -> libc::c_int {
+    (obj._private == TRUE) as libc::c_int
+}
+
+#[no_mangle]
+pub extern "C" fn My_IsFalse(obj: PytObjectPointer) -> libc::c_int {
+    (obj._private == FALSE) as libc::c_int
+}
+
+#[no_mangle]
+pub extern "C" fn My_Is(obj0: PytObjectPointer, obj1: PytObjectPointer) -> libc::c_int {
+    (obj0 == obj1) as libc::c_int
+}
+
+#[no_mangle]
+pub extern "C" fn My_Type(obj: PytObjectPointer) -> PytObjectPointer {
+    mock_ptr(with_heap(|h| h.get(obj._private).typ))
+}
+
+#[no_mangle]
+pub extern "C" fn My_IsType(obj: PytObjectPointer, typ: PytObjectPointer) -> libc::c_int {
+    mock_is_type(obj, typ._private)
+}
+
+#[no_mangle]
+pub extern "C" fn MyType_TypeNoIncRef() -> PytObjectPointer {
+    mock_ptr(TYPE_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyNone_TypeNoIncRef() -> PytObjectPointer {
+    mock_ptr(NONE_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyBool_TypeNoIncRef() -> PytObjectPointer {
+    mock_ptr(BOOL_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyLong_TypeNoIncRef() -> PytObjectPointer {
+    mock_ptr(LONG_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyUnicode_TypeNoIncRef() -> PytObjectPointer {
+    mock_ptr(UNICODE_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyList_TypeNoIncRef() -> PytObjectPointer {
+    mock_ptr(LIST_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyTuple_TypeNoIncRef() -> PytObjectPointer {
+    mock_ptr(TUPLE_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyDict_TypeNoIncRef() -> PytObjectPointer {
+    mock_ptr(DICT_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyModule_TypeNoIncRef() -> PytObjectPointer {
+    mock_ptr(MODULE_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyFloat_TypeNoIncRef() -> PytObjectPointer {
+    mock_ptr(FLOAT_TYPE)
+}
+
+#[no_mangle]
+pub extern "C" fn MyBytes_TypeNoIncRef() -> PytObjectPointer {
+    mock_ptr(BYTES_TYPE)
+}
+
+// module attributes, or whatever the type's `tp_getattro` returns
+#[no_mangle]
+pub unsafe extern "C" fn PyObject_GetAttr(obj: PytObjectPointer, name: PytObjectPointer) -> PytObjectPointer {
+    let lookup = with_heap(|h| {
+        let attr = match &h.get(name._private).data {
+            MockData::Unicode(attr) => attr.clone(),
+            _ => return Err((TYPE_ERROR, "attribute name must be string".to_owned())),
+        };
+        if let Some(getattro) = h.typ(obj._private).getattro {
+            return Ok(Err(getattro));
+        }
+        let value = match &h.get(obj._private).data {
+            MockData::Module { attrs, .. } => attrs.iter().find(|(n, _)| *n == attr).map(|&(_, value)| value),
+            _ => None,
+        };
+        match value {
+            Some(value) => {
+                h.incref(value);
+                Ok(Ok(value))
+            }
+            None => Err((ATTRIBUTE_ERROR, h.attr_error(obj._private, &attr))),
+        }
+    });
+    match lookup {
+        Ok(Ok(value)) => mock_ptr(value),
+        Ok(Err(getattro)) => getattro(obj, name),
+        Err((exc, message)) => mock_fail(exc, &message),
+    }
+}
+
+// only modules have attributes that can be set
+#[no_mangle]
+pub unsafe extern "C" fn PyObject_SetAttr(obj: PytObjectPointer, name: PytObjectPointer, value: PytObjectPointer) -> libc::c_int {
+    let attr = with_heap(|h| match (&h.get(name._private).data, &h.get(obj._private).data) {
+        (MockData::Unicode(attr), MockData::Module { .. }) => Ok(attr.clone()),
+        (MockData::Unicode(attr), _) => Err((ATTRIBUTE_ERROR, h.attr_error(obj._private, attr))),
+        _ => Err((TYPE_ERROR, "attribute name must be string".to_owned())),
+    });
+    match attr {
+        Ok(attr) => {
+            Py_IncRef(value);
+            mock_module_setattr(obj._private, &attr, value._private)
+        }
+        Err((exc, message)) => {
+            mock_fail(exc, &message);
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyObject_HasAttr(obj: PytObjectPointer, name: PytObjectPointer) -> libc::c_int {
+    let value = PyObject_GetAttr(obj, name);
+    if value.is_null() {
+        PyErr_Clear();
+        0
+    } else {
+        Py_DecRef(value);
+        1
+    }
+}
+
+// only functions of modules created by `MyModule_Create` can be called; `args` is a tuple
+unsafe fn mock_call(callable: PytObjectPointer, args: &[PytObjectPointer]) -> PytObjectPointer {
+    let function = with_heap(|h| match h.get(callable._private).data {
+        MockData::Function { def, module } => Ok((&*def, module)),
+        _ => Err(h.type_name(callable._private)),
+    });
+    let (def, module) = match function {
+        Ok(function) => function,
+        Err(name) => return mock_fail(TYPE_ERROR, &format!("'{}' object is not callable", name)),
+    };
+    let name = CStr::from_ptr(def.ml_name).to_string_lossy();
+    let result = match def.ml_flags {
+        METH_NOARGS if args.is_empty() => (def.ml_meth)(mock_ptr(module), mock_ptr(0)),
+        METH_NOARGS => return mock_fail(TYPE_ERROR, &format!("{}() takes no arguments ({} given)", name, args.len())),
+        METH_O if args.len() == 1 => (def.ml_meth)(mock_ptr(module), args[0]),
+        METH_O => return mock_fail(TYPE_ERROR, &format!("{}() takes exactly one argument ({} given)", name, args.len())),
+        METH_VARARGS => {
+            let tuple = mock_seq_new(TUPLE_TYPE, args.len() as pyt_ssize_t);
+            for (i, &arg) in args.iter().enumerate() {
+                Py_IncRef(arg);
+                mock_seq_setitem(tuple, TUPLE_TYPE, i as pyt_ssize_t, arg);
+            }
+            let result = (def.ml_meth)(mock_ptr(module), tuple);
+            Py_DecRef(tuple);
+            result
+        }
+        flags => panic!("mock: unsupported calling convention {:#x}", flags),
+    };
+    // what `_Py_CheckFunctionResult` checks after every call
+    let error_set = with_heap(|h| h.error.is_some());
+    if result.is_null() && !error_set {
+        return mock_fail(SYSTEM_ERROR, &format!("{} returned NULL without setting an exception", name));
+    }
+    if !result.is_null() && error_set {
+        Py_DecRef(result);
+        return mock_fail(SYSTEM_ERROR, &format!("{} returned a result with an exception set", name));
+    }
+    result
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyObject_CallNoArgs(callable: PytObjectPointer) -> PytObjectPointer {
+    mock_call(callable, &[])
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyObject_CallOneArg(callable: PytObjectPointer, arg: PytObjectPointer) -> PytObjectPointer {
+    mock_call(callable, &[arg])
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyObject_CallObject(callable: PytObjectPointer, args: PytObjectPointer) -> PytObjectPointer {
+    if args.is_null() {
+        return mock_call(callable, &[]);
+    }
+    let args = with_heap(|h| match &h.get(args._private).data {
+        MockData::Tuple(items) => Some(items.iter().map(|&item| mock_ptr(item)).collect::<Vec<_>>()),
+        _ => None,
+    });
+    match args {
+        Some(args) => mock_call(callable, &args),
+        None => mock_fail(TYPE_ERROR, "argument list must be a tuple"),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyObject_GetAttrString(obj: PytObjectPointer, name: *const libc::c_char) -> PytObjectPointer {
+    let name = PyUnicode_FromString(name);
+    if name.is_null() {
+        return name;
+    }
+    let result = PyObject_GetAttr(obj, name);
+    Py_DecRef(name);
+    result
+}
+
+// numbers by value, str and bytes lexicographically, anything else by identity (`==` and `!=` only)
+#[no_mangle]
+pub extern "C" fn PyObject_RichCompare(obj0: PytObjectPointer, obj1: PytObjectPointer, op: libc::c_int) -> PytObjectPointer {
+    use std::cmp::Ordering;
+    let result = with_heap(|h| {
+        let (a, b) = (obj0._private, obj1._private);
+        let ordering = match (&h.get(a).data, &h.get(b).data) {
+            (MockData::Unicode(x), MockData::Unicode(y)) => Some(x.cmp(y)),
+            (MockData::Bytes(x), MockData::Bytes(y)) => Some(x.cmp(y)),
+            _ => match (h.as_int(a), h.as_int(b)) {
+                (Some(x), Some(y)) => Some(x.cmp(&y)),
+                _ => h.as_float(a).zip(h.as_float(b)).map(|(x, y)| x.partial_cmp(&y).unwrap_or(Ordering::Less)),
+            },
+        };
+        let v = match (ordering, op) {
+            (Some(o), 0) => o == Ordering::Less,
+            (Some(o), 1) => o != Ordering::Greater,
+            (Some(o), 2) => o == Ordering::Equal,
+            (Some(o), 3) => o != Ordering::Equal,
+            (Some(o), 4) => o == Ordering::Greater,
+            (Some(o), 5) => o != Ordering::Less,
+            (None, 2) => a == b,
+            (None, 3) => a != b,
+            _ => return Err(format!("comparison not supported between instances of '{}' and '{}'", h.type_name(a), h.type_name(b))),
+        };
+        Ok(h.new_bool(v))
+    });
+    match result {
+        Ok(result) => mock_ptr(result),
+        Err(message) => mock_fail(TYPE_ERROR, &message),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn PyObject_IsTrue(obj: PytObjectPointer) -> libc::c_int {
+    with_heap(|h| {
+        let obj = obj._private;
+        let v = match &h.get(obj).data {
+            MockData::Constant => obj == TRUE,
+            MockData::Long(v) => *v != 0,
+            MockData::Float(v) => *v != 0.0,
+            MockData::Unicode(s) => !s.as_bytes().is_empty(),
+            MockData::Bytes(bytes) => bytes.len() > 1,
+            MockData::List(items) | MockData::Tuple(items) => !items.is_empty(),
+            _ => true,
+        };
+        v as libc::c_int
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn PyObject_GetIter(obj: PytObjectPointer) -> PytObjectPointer {
+    let iter = with_heap(|h| {
+        let obj = obj._private;
+        match &h.get(obj).data {
+            MockData::List(_) | MockData::Tuple(_) => {
+                h.incref(obj);
+                Ok(h.alloc(ITERATOR_TYPE, MockData::Iterator { seq: obj, index: 0 }))
+            }
+            MockData::Iterator { .. } => {
+                h.incref(obj);
+                Ok(obj)
+            }
+            _ => Err(format!("'{}' object is not iterable", h.type_name(obj))),
+        }
+    });
+    match iter {
+        Ok(iter) => mock_ptr(iter),
+        Err(message) => mock_fail(TYPE_ERROR, &message),
+    }
+}
+
+// NULL without an error set once it is exhausted; it lets go of the sequence then
+#[no_mangle]
+pub extern "C" fn PyIter_Next(iter: PytObjectPointer) -> PytObjectPointer {
+    let next = with_heap(|h| {
+        let (seq, index) = match h.get(iter._private).data {
+            MockData::Iterator { seq, index } => (seq, index),
+            _ => return Err(format!("'{}' object is not an iterator", h.type_name(iter._private))),
+        };
+        if seq == 0 {
+            return Ok((0, 0));
+        }
+        match mock_items(h, seq).unwrap().get(index as usize).copied() {
+            Some(item) => {
+                h.incref(item);
+                h.get_mut(iter._private).data = MockData::Iterator { seq, index: index + 1 };
+                Ok((item, 0))
+            }
+            None => {
+                h.get_mut(iter._private).data = MockData::Iterator { seq: 0, index };
+                Ok((0, seq))
+            }
+        }
+    });
+    match next {
+        Ok((item, exhausted)) => {
+            mock_release(&[exhausted]);
+            mock_ptr(item)
+        }
+        Err(message) => mock_fail(TYPE_ERROR, &message),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MockNumberOp {
+    Add,
+    Subtract,
+    Multiply,
+    FloorDivide,
+    Power,
+}
+
+impl MockNumberOp {
+    fn symbol(&self) -> &'static str {
+        match self {
+            MockNumberOp::Add => "+",
+            MockNumberOp::Subtract => "-",
+            MockNumberOp::Multiply => "*",
+            MockNumberOp::FloorDivide => "//",
+            MockNumberOp::Power => "** or pow()",
+        }
+    }
+
+    fn ints(&self, a: i128, b: i128) -> Result<MockData, (usize, &'static str)> {
+        let overflow = (OVERFLOW_ERROR, "the mock only supports ints that fit in 128 bits");
+        let v = match self {
+            MockNumberOp::Add => a.checked_add(b),
+            MockNumberOp::Subtract => a.checked_sub(b),
+            MockNumberOp::Multiply => a.checked_mul(b),
+            MockNumberOp::FloorDivide if b == 0 => return Err((ZERO_DIVISION_ERROR, "integer division or modulo by zero")),
+            MockNumberOp::FloorDivide => a.checked_div(b).map(|q| if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q }),
+            MockNumberOp::Power if b < 0 => return self.floats(a as f64, b as f64),
+            MockNumberOp::Power => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+        };
+        v.map(MockData::Long).ok_or(overflow)
+    }
+
+    fn floats(&self, a: f64, b: f64) -> Result<MockData, (usize, &'static str)> {
+        let v = match self {
+            MockNumberOp::Add => a + b,
+            MockNumberOp::Subtract => a - b,
+            MockNumberOp::Multiply => a * b,
+            MockNumberOp::FloorDivide if b == 0.0 => return Err((ZERO_DIVISION_ERROR, "float floor division by zero")),
+            MockNumberOp::FloorDivide => (a / b).floor(),
+            MockNumberOp::Power if a == 0.0 && b < 0.0 => return Err((ZERO_DIVISION_ERROR, "0.0 cannot be raised to a negative power")),
+            MockNumberOp::Power if a < 0.0 && b.fract() != 0.0 => return Err((VALUE_ERROR, "the mock doesn't support complex numbers")),
+            MockNumberOp::Power => a.powf(b),
+        };
+        Ok(MockData::Float(v))
+    }
+}
+
+// ints and floats, plus `nb_add` of types created by `MyType_FromSlots`
+unsafe fn mock_number(op: MockNumberOp, obj0: PytObjectPointer, obj1: PytObjectPointer) -> PytObjectPointer {
+    let (a, b) = (obj0._private, obj1._private);
+    let result = with_heap(|h| {
+        let data = match (h.as_int(a), h.as_int(b)) {
+            (Some(x), Some(y)) => Some(op.ints(x, y)),
+            _ => h.as_float(a).zip(h.as_float(b)).map(|(x, y)| op.floats(x, y)),
+        };
+        let slots = if op == MockNumberOp::Add { [h.typ(a).nb_add, h.typ(b).nb_add] } else { [None, None] };
+        let message = format!("unsupported operand type(s) for {}: '{}' and '{}'", op.symbol(), h.type_name(a), h.type_name(b));
+        match data {
+            Some(Ok(data)) => {
+                match data {
+                    MockData::Long(v) => Ok(Ok(h.new_long(v))),
+                    data => Ok(Ok(h.alloc(FLOAT_TYPE, data))),
+                }
+            }
+            Some(Err(error)) => Err((error.0, error.1.to_owned())),
+            None => Ok(Err((slots, message))),
+        }
+    });
+    let (slots, message) = match result {
+        Ok(Ok(result)) => return mock_ptr(result),
+        Ok(Err(slots)) => slots,
+        Err((exc, message)) => return mock_fail(exc, &message),
+    };
+    // the left operand's slot first, then the right one's if it is a different slot
+    let mut tried = None;
+    for slot in slots.into_iter().flatten() {
+        if tried == Some(slot as usize) {
+            continue;
+        }
+        tried = Some(slot as usize);
+        let result = slot(obj0, obj1);
+        if result._private != NOT_IMPLEMENTED {
+            return result;
+        }
+        Py_DecRef(result);
+    }
+    mock_fail(TYPE_ERROR, &message)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyNumber_Add(obj0: PytObjectPointer, obj1: PytObjectPointer) -> PytObjectPointer {
+    mock_number(MockNumberOp::Add, obj0, obj1)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyNumber_Subtract(obj0: PytObjectPointer, obj1: PytObjectPointer) -> PytObjectPointer {
+    mock_number(MockNumberOp::Subtract, obj0, obj1)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyNumber_Multiply(obj0: PytObjectPointer, obj1: PytObjectPointer) -> PytObjectPointer {
+    mock_number(MockNumberOp::Multiply, obj0, obj1)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyNumber_FloorDivide(obj0: PytObjectPointer, obj1: PytObjectPointer) -> PytObjectPointer {
+    mock_number(MockNumberOp::FloorDivide, obj0, obj1)
+}
+
+// the modulus has to be None
+#[no_mangle]
+pub unsafe extern "C" fn PyNumber_Power(obj0: PytObjectPointer, obj1: PytObjectPointer, obj2: PytObjectPointer) -> PytObjectPointer {
+    assert!(obj2._private == NONE, "mock: pow() with a modulus isn't supported");
+    mock_number(MockNumberOp::Power, obj0, obj1)
+}
+
+#[no_mangle]
+pub extern "C" fn PyErr_Occurred() -> PytObjectPointer {
+    mock_ptr(with_heap(|h| h.error.map_or(0, |(typ, _, _)| typ)))
+}
+
+#[no_mangle]
+pub extern "C" fn PyErr_Clear() {
+    mock_release_error(with_heap(|h| h.error.take()));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyErr_SetString(exc: PytObjectPointer, message: *const libc::c_char) {
+    mock_fail(exc._private, &CStr::from_ptr(message).to_string_lossy());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyErr_Fetch(ptype: *mut PytObjectPointer, pvalue: *mut PytObjectPointer, ptraceback: *mut PytObjectPointer) {
+    let (typ, value, traceback) = with_heap(|h| h.error.take()).unwrap_or((0, 0, 0));
+    *ptype = mock_ptr(typ);
+    *pvalue = mock_ptr(value);
+    *ptraceback = mock_ptr(traceback);
+}
+
+#[no_mangle]
+pub extern "C" fn PyErr_Restore(typ: PytObjectPointer, value: PytObjectPointer, traceback: PytObjectPointer) {
+    let error = if typ.is_null() {
+        mock_release(&[value._private, traceback._private]);
+        None
+    } else {
+        Some((typ._private, value._private, traceback._private))
+    };
+    mock_release_error(with_heap(|h| std::mem::replace(&mut h.error, error)));
+}
+
+#[no_mangle]
+pub extern "C" fn PyErr_ExceptionMatches(exc: PytObjectPointer) -> libc::c_int {
+    with_heap(|h| h.error.is_some_and(|(typ, _, _)| typ == exc._private) as libc::c_int)
+}
+
+// only bytes export buffers
+#[no_mangle]
+pub unsafe extern "C" fn PyObject_GetBuffer(obj: PytObjectPointer, view: *mut PytBuffer, _flags: libc::c_int) -> libc::c_int {
+    let exported = with_heap(|h| match &mut h.get_mut(obj._private).data {
+        MockData::Bytes(bytes) => {
+            let (buf, len) = (bytes.as_mut_ptr(), bytes.len() - 1);
+            h.incref(obj._private);
+            Ok((buf, len))
+        }
+        _ => Err(format!("a bytes-like object is required, not '{}'", h.type_name(obj._private))),
+    });
+    match exported {
+        Ok((buf, len)) => {
+            view.write(PytBuffer {
+                buf: buf as *mut libc::c_void,
+                obj,
+                len: len as pyt_ssize_t,
+                itemsize: 1,
+                readonly: 1,
+                ndim: 1,
+                format: std::ptr::null_mut(),
+                shape: std::ptr::null_mut(),
+                strides: std::ptr::null_mut(),
+                suboffsets: std::ptr::null_mut(),
+                internal: std::ptr::null_mut(),
+            });
+            0
+        }
+        Err(message) => {
+            mock_fail(TYPE_ERROR, &message);
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PyBuffer_Release(view: *mut PytBuffer) {
+    let obj = std::mem::replace(&mut (*view).obj, mock_ptr(0));
+    mock_release(&[obj._private]);
+}
+
+// the functions hold a reference to the module, like `m_self` of a `PyCFunction`
+#[no_mangle]
+pub unsafe extern "C" fn MyModule_Create(name: *const libc::c_char, _doc: *const libc::c_char, methods: *const PytMethodDef, count: pyt_ssize_t) -> PytObjectPointer {
+    let module = PyModule_New(name);
+    for i in 0..count as usize {
+        let def = methods.add(i);
+        let function = with_heap(|h| {
+            h.incref(module._private);
+            h.alloc(FUNCTION_TYPE, MockData::Function { def, module: module._private })
+        });
+        mock_module_setattr(module._private, CStr::from_ptr((*def).ml_name), function);
+    }
+    module
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn MyType_FromSlots(name: *const libc::c_char, dealloc: PytDestructor, getattro: Option<PytGetAttroFunc>,
+    _repr: Option<PytReprFunc>, nb_add: Option<PytBinaryFunc>) -> PytObjectPointer {
+    let typ = MockType { name: CStr::from_ptr(name).to_owned(), dealloc: Some(dealloc), getattro, nb_add };
+    mock_ptr(with_heap(|h| h.alloc(TYPE_TYPE, MockData::Type(typ))))
+}
+
+#[no_mangle]
+pub extern "C" fn MyType_Dealloc(typ: PytObjectPointer) -> Option<PytDestructor> {
+    with_heap(|h| match &h.get(typ._private).data {
+        MockData::Type(t) => t.dealloc,
+        _ => None,
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn MyType_Name(typ: PytObjectPointer) -> *const libc::c_char {
+    with_heap(|h| match &h.get(typ._private).data {
+        MockData::Type(t) => t.name.as_ptr(),
+        _ => std::ptr::null(),
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn MyInstance_New(typ: PytObjectPointer, data: *mut libc::c_void) -> PytObjectPointer {
+    mock_ptr(with_heap(|h| {
+        h.incref(typ._private);
+        h.alloc(typ._private, MockData::Instance(data))
+    }))
+}
+
+#[no_mangle]
+pub extern "C" fn MyInstance_Data(obj: PytObjectPointer) -> *mut libc::c_void {
+    with_heap(|h| match h.get(obj._private).data {
+        MockData::Instance(data) => data,
+        _ => panic!("mock: {:#x} isn't an instance", obj._private),
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn MyInstance_Free(obj: PytObjectPointer) {
+    let typ = with_heap(|h| {
+        let typ = h.get(obj._private).typ;
+        h.objects[(obj._private - MOCK_BASE) / MOCK_ALIGN] = None;
+        typ
+    });
+    mock_release(&[typ]);
+}
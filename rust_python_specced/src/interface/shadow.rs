@@ -0,0 +1,139 @@
+// Runtime checking of the specs (the `shadow-heap` feature). The ghost parts of `GpyGlobalState`
+// become real: `refs_held` and `error` are kept up to date, and `ShadowHeap` records what the
+// specs promise about the objects they handed out (their type and ghost data). The checked
+// wrappers compare that, and the reference counts they promise to change, against what CPython
+// reports, and panic on a mismatch, so test runs catch specs that don't describe what CPython
+// actually does.
+//
+// Reference counts are only compared within a single call (before vs. after), since code that
+// doesn't go through the wrappers (`Owned::drop`, Python code) changes them all the time. For the
+// same reason the heap forgets everything once Python code ran or a container was freed: either
+// may free objects whose addresses get reused afterwards.
+
+use std::collections::HashMap;
+
+use super::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ShadowData {
+    None,
+    Bool(bool),
+    NotImplemented,
+    Long(Option<libc::c_long>),
+    Float(f64),
+    Bytes(pyt_ssize_t),
+    Unicode,
+    List,
+    Tuple(pyt_ssize_t),
+    Dict,
+    Module,
+    Other,
+}
+
+impl ShadowData {
+    // whether freeing such an object can free others as well
+    fn is_container(&self) -> bool {
+        matches!(self, ShadowData::List | ShadowData::Tuple(_) | ShadowData::Dict | ShadowData::Module | ShadowData::Other)
+    }
+}
+
+#[derive(Default)]
+pub struct ShadowHeap {
+    objects: HashMap<usize, ShadowData>,
+}
+
+#[track_caller]
+unsafe fn check_data(obj: PytObjectPointer, data: ShadowData) {
+    let matches = match data {
+        ShadowData::None => My_IsNone(obj) != 0,
+        ShadowData::Bool(true) => My_IsTrue(obj) != 0,
+        ShadowData::Bool(false) => My_IsFalse(obj) != 0,
+        ShadowData::NotImplemented => obj == MyNotImplemented_GetNoIncRef(),
+        ShadowData::Long(v) => MyLong_CheckExact(obj) != 0 && v.map_or(true, |v| PyLong_AsLong(obj) == v),
+        ShadowData::Float(v) => MyFloat_CheckExact(obj) != 0 && (PyFloat_AsDouble(obj) == v || v.is_nan()),
+        ShadowData::Bytes(len) => MyBytes_CheckExact(obj) != 0 && PyBytes_Size(obj) == len,
+        ShadowData::Unicode => MyUnicode_CheckExact(obj) != 0,
+        ShadowData::List => MyList_CheckExact(obj) != 0,
+        ShadowData::Tuple(len) => MyTuple_CheckExact(obj) != 0 && PyTuple_Size(obj) == len,
+        ShadowData::Dict => MyDict_CheckExact(obj) != 0,
+        ShadowData::Module => My_IsType(obj, MyModule_TypeNoIncRef()) != 0,
+        ShadowData::Other => true,
+    };
+    assert!(matches, "shadow heap: object {:#x} is not {:?}", obj._private, data);
+}
+
+impl ShadowHeap {
+    // before a wrapper uses `obj`: what the specs said about it so far must still hold
+    #[track_caller]
+    pub unsafe fn observe(&self, obj: PytObjectPointer) {
+        if let Some(&data) = self.objects.get(&obj._private) {
+            check_data(obj, data);
+        }
+    }
+
+    // the data of `obj` as recorded, if it is known
+    pub fn get(&self, obj: PytObjectPointer) -> Option<ShadowData> {
+        self.objects.get(&obj._private).copied()
+    }
+
+    // `obj` (new or not) is described by `data` from now on
+    #[track_caller]
+    pub unsafe fn record(&mut self, obj: PytObjectPointer, data: ShadowData) {
+        check_data(obj, data);
+        self.objects.insert(obj._private, data);
+    }
+
+    // `obj` is owned by something that changes it without telling us (e.g. an `Owned` handle)
+    pub fn forget(&mut self, obj: PytObjectPointer) {
+        self.objects.remove(&obj._private);
+    }
+
+    // the reference count of `obj` was `before` and went up by `delta` (`ref_count_changed_by`);
+    // it can't be read once it dropped to zero, since the object is gone then
+    #[track_caller]
+    pub unsafe fn changed_by(&mut self, obj: PytObjectPointer, before: pyt_ssize_t, delta: pyt_ssize_t) {
+        if before + delta == 0 {
+            match self.objects.remove(&obj._private) {
+                Some(data) if !data.is_container() => {}
+                _ => self.havoc(),
+            }
+            return;
+        }
+        let actual = My_REFCNT(obj);
+        // the count an immortal object reports differs between versions, so CPython is asked
+        let immortal = actual == before && My_IsImmortal(obj) != 0;
+        assert!(immortal || actual == before + delta,
+            "shadow heap: object {:#x} has {} references instead of {}", obj._private, actual, before + delta);
+    }
+
+    // `gpy_havoc`: the specs keep immutable data, but we can't tell which objects are still alive
+    pub fn havoc(&mut self) {
+        self.objects.clear();
+    }
+}
+
+// the exception types `PytExceptionType` can tell apart, in the order they are tried
+const EXCEPTION_TYPES: [PytExceptionType; 9] = [
+    PytExceptionType::OverflowError,
+    PytExceptionType::ZeroDivisionError,
+    PytExceptionType::MemoryError,
+    PytExceptionType::TypeError,
+    PytExceptionType::ValueError,
+    PytExceptionType::IndexError,
+    PytExceptionType::KeyError,
+    PytExceptionType::AttributeError,
+    PytExceptionType::SystemError,
+];
+
+// the error indicator as the specs see it
+pub unsafe fn current_error() -> GpyErrorState {
+    if PyErr_Occurred().is_null() {
+        return GpyErrorState::NoError;
+    }
+    for exc in EXCEPTION_TYPES {
+        if PyErr_ExceptionMatches(exc.as_ptr()) != 0 {
+            return GpyErrorState::Set(exc);
+        }
+    }
+    GpyErrorState::Set(PytExceptionType::Other)
+}
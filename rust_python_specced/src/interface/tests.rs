@@ -0,0 +1,339 @@
+// Runtime tests of the wrappers against the mock backend (`cargo test --features mock-python`). They
+// check what the specs promise about reference counts, errors and stolen references, and what
+// CPython does where the specs say nothing (a call breaking a precondition). There is only one
+// interpreter token, so the tests take turns with it.
+
+use std::ffi::CStr;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::thread;
+
+use super::handle::Owned;
+use super::mock::mock_is_alive;
+use super::*;
+
+static TOKEN: Mutex<Option<PytInterpreter>> = Mutex::new(None);
+
+// runs `f` in a fresh interpreter, which is finalized afterwards even if `f` panics (or leaves
+// references behind, which the shadow heap reports when finalizing)
+fn with_interpreter(f: impl FnOnce(&mut GpyGlobalState)) {
+    let mut token = TOKEN.lock().unwrap_or_else(|e| e.into_inner());
+    let mut s = pyt_initialize(token.take().or_else(PytInterpreter::take).unwrap());
+    let result = panic::catch_unwind(AssertUnwindSafe(|| f(&mut s)));
+    let leaked = s.refs_held;
+    s.refs_held = 0;
+    *token = Some(pyt_finalize(s));
+    if let Err(e) = result {
+        panic::resume_unwind(e);
+    }
+    assert!(!cfg!(feature = "shadow-heap") || leaked == 0, "shadow heap: {} references were never given back", leaked);
+}
+
+fn cstr(s: &[u8]) -> &CStr {
+    CStr::from_bytes_with_nul(s).unwrap()
+}
+
+unsafe fn long(v: libc::c_long, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let obj = pytlong_fromlong(v, s);
+    assert!(!obj.is_null());
+    obj
+}
+
+// the error is `exc`; clears it
+unsafe fn take_error(exc: PytExceptionType, s: &mut GpyGlobalState) {
+    assert!(pyt_err_occurred(s));
+    assert!(pyt_err_exceptionmatches(exc, s));
+    pyt_err_clear(s);
+}
+
+#[test]
+fn incref_and_decref() {
+    with_interpreter(|s| unsafe {
+        let obj = long(1000, s);
+        assert_eq!(pyt_refcnt(obj, s), 1);
+        pyt_incref(obj, s);
+        assert_eq!(pyt_refcnt(obj, s), 2);
+        pyt_decref(obj, s);
+        assert_eq!(pyt_refcnt(obj, s), 1);
+        pyt_decref(obj, s);
+        assert!(!mock_is_alive(obj));
+    });
+}
+
+#[test]
+fn constants_get_a_new_reference() {
+    with_interpreter(|s| unsafe {
+        let before = pyt_refcnt(pytnone_getnoincref(s), s);
+        let none = pytnone_get(s);
+        assert!(none == pytnone_getnoincref(s));
+        assert_eq!(pyt_refcnt(none, s), before + 1);
+        pyt_decref(none, s);
+        assert_eq!(pyt_refcnt(none, s), before);
+    });
+}
+
+#[test]
+fn small_ints_and_short_strings_are_shared() {
+    with_interpreter(|s| unsafe {
+        let (a, b) = (long(256, s), long(256, s));
+        assert!(a == b);
+        let (c, d) = (long(257, s), long(257, s));
+        assert!(c != d);
+        let (e, f) = (pytunicode_fromstring(cstr(b"x\0"), s), pytunicode_fromstring(cstr(b"x\0"), s));
+        assert!(e == f);
+        let (g, h) = (pytbytes_fromslice(b"", s), pytbytes_fromslice(b"", s));
+        assert!(g == h);
+        for obj in [a, b, c, d, e, f, g, h] {
+            pyt_decref(obj, s);
+        }
+        assert!(mock_is_alive(a) && mock_is_alive(e) && mock_is_alive(g));
+        assert!(!mock_is_alive(c) && !mock_is_alive(d));
+    });
+}
+
+#[test]
+fn list_setitem_steals_and_releases_the_replaced_item() {
+    with_interpreter(|s| unsafe {
+        let list = pytlist_new(2, s);
+        let (a, b) = (long(1001, s), long(1002, s));
+        assert_eq!(pytlist_setitem(list, 0, a, s), 0);
+        assert_eq!(pyt_refcnt(a, s), 1);
+        assert_eq!(pytlist_setitem(list, 0, b, s), 0);
+        assert!(!mock_is_alive(a));
+        assert!(pytlist_getitem(list, 0, s) == b);
+        assert_eq!(pyt_refcnt(b, s), 1);
+        // a slot that was never filled in is NULL, without an error
+        assert!(pytlist_getitem(list, 1, s).is_null());
+        assert!(!pyt_err_occurred(s));
+        pyt_decref(list, s);
+        assert!(!mock_is_alive(b));
+    });
+}
+
+#[test]
+fn list_index_out_of_range() {
+    with_interpreter(|s| unsafe {
+        let list = pytlist_new(1, s);
+        assert!(pytlist_getitem(list, 1, s).is_null());
+        take_error(PytExceptionType::IndexError, s);
+        assert!(pytlist_getitem(list, -1, s).is_null());
+        take_error(PytExceptionType::IndexError, s);
+        // the item is stolen even though it isn't stored
+        let item = long(1003, s);
+        assert_eq!(pytlist_setitem(list, 1, item, s), -1);
+        take_error(PytExceptionType::IndexError, s);
+        assert!(!mock_is_alive(item));
+        pyt_decref(list, s);
+    });
+}
+
+#[test]
+fn tuple_setitem_and_index_out_of_range() {
+    with_interpreter(|s| unsafe {
+        let tuple = pyttuple_new(1, s);
+        let item = long(1004, s);
+        assert_eq!(pyttuple_setitem(tuple, 0, item, s), 0);
+        assert!(pyttuple_getitem(tuple, 0, s) == item);
+        assert!(pyttuple_getitem(tuple, 1, s).is_null());
+        take_error(PytExceptionType::IndexError, s);
+        let item = long(1005, s);
+        assert_eq!(pyttuple_setitem(tuple, -1, item, s), -1);
+        take_error(PytExceptionType::IndexError, s);
+        assert!(!mock_is_alive(item));
+        pyt_decref(tuple, s);
+    });
+}
+
+// outside of the spec (which only allows filling in a tuple nobody else refers to): CPython refuses
+// with a SystemError, and still releases the item
+#[test]
+fn tuple_setitem_on_a_shared_tuple() {
+    with_interpreter(|s| unsafe {
+        let tuple = pyttuple_new(1, s);
+        pyt_incref(tuple, s);
+        let item = long(1006, s);
+        assert_eq!(PyTuple_SetItem(tuple, 0, item), -1);
+        // called directly, so nothing else accounts for the stolen reference
+        #[cfg(feature = "shadow-heap")]
+        {
+            s.refs_held -= 1;
+        }
+        take_error(PytExceptionType::SystemError, s);
+        assert!(!mock_is_alive(item));
+        pyt_decref(tuple, s);
+        pyt_decref(tuple, s);
+    });
+}
+
+#[test]
+fn fetch_and_restore_an_error() {
+    with_interpreter(|s| unsafe {
+        pyt_err_setstring(PytExceptionType::ValueError, cstr(b"bad value\0"), s);
+        let err = pyt_err_fetch(s);
+        assert!(!pyt_err_occurred(s));
+        assert!(err.typ == PytExceptionType::ValueError.as_ptr());
+        // the type and the message, but no traceback
+        assert_eq!(err.refs, 2);
+        assert!(err.traceback.is_null());
+        let value = err.value;
+        assert_eq!(pyt_refcnt(value, s), 1);
+        pyt_err_restore(err, s);
+        assert_eq!(pyt_refcnt(value, s), 1);
+        take_error(PytExceptionType::ValueError, s);
+        assert!(!mock_is_alive(value));
+    });
+}
+
+#[test]
+fn fetch_without_an_error() {
+    with_interpreter(|s| unsafe {
+        let err = pyt_err_fetch(s);
+        assert!(err.typ.is_null() && err.value.is_null() && err.traceback.is_null());
+        assert_eq!(err.refs, 0);
+        pyt_err_restore(err, s);
+        assert!(!pyt_err_occurred(s));
+    });
+}
+
+#[test]
+fn compare_ints_and_other_objects() {
+    with_interpreter(|s| unsafe {
+        let (a, b) = (long(1014, s), long(1015, s));
+        let lt = pytobject_richcompare(a, b, PytCompareOp::Lt, s);
+        assert!(lt == pytbool_gettruenoincref(s));
+        let eq = pytobject_richcompare(a, b, PytCompareOp::Eq, s);
+        assert!(eq == pytbool_getfalsenoincref(s));
+        // only == and != fall back to identity
+        let text = pytunicode_fromstring(cstr(b"spam\0"), s);
+        assert!(pytobject_richcompare(a, text, PytCompareOp::Lt, s).is_null());
+        take_error(PytExceptionType::TypeError, s);
+        let ne = pytobject_richcompare(a, text, PytCompareOp::Ne, s);
+        assert!(ne == pytbool_gettruenoincref(s));
+        for obj in [lt, eq, ne, text, a, b] {
+            pyt_decref(obj, s);
+        }
+    });
+}
+
+#[test]
+fn attributes_of_a_module() {
+    with_interpreter(|s| unsafe {
+        let module = pytmodule_new(cstr(b"spam\0"), s);
+        let name = pytunicode_fromstring(cstr(b"eggs\0"), s);
+        assert!(!pytobject_hasattr(module, name, s));
+        assert!(!pyt_err_occurred(s));
+        assert!(pytobject_getattr(module, name, s).is_null());
+        take_error(PytExceptionType::AttributeError, s);
+        let value = long(1013, s);
+        assert_eq!(pytobject_setattr(module, name, value, s), 0);
+        assert!(pytobject_hasattr(module, name, s));
+        let attr = pytobject_getattr(module, name, s);
+        assert!(attr == value);
+        assert_eq!(pyt_refcnt(value, s), 3);
+        for obj in [attr, value, name, module] {
+            pyt_decref(obj, s);
+        }
+        assert!(!mock_is_alive(value));
+    });
+}
+
+#[test]
+fn handles_release_their_reference_when_dropped() {
+    with_interpreter(|s| {
+        let obj = Owned::from_long(1007, s).unwrap();
+        let ptr = obj.ptr();
+        let copy = obj.clone();
+        assert_eq!(unsafe { pyt_refcnt(ptr, s) }, 2);
+        drop(copy);
+        assert_eq!(unsafe { pyt_refcnt(ptr, s) }, 1);
+        drop(obj);
+        assert!(!mock_is_alive(ptr));
+    });
+}
+
+#[test]
+fn handles_into_raw_keeps_the_reference() {
+    with_interpreter(|s| unsafe {
+        let ptr = Owned::from_long(1008, s).unwrap().into_raw(s);
+        assert_eq!(pyt_refcnt(ptr, s), 1);
+        let obj = Owned::from_raw(ptr, s);
+        obj.release(s);
+        assert!(!mock_is_alive(ptr));
+    });
+}
+
+#[test]
+fn handles_iterate_over_a_list() {
+    with_interpreter(|s| {
+        let mut list = Owned::new_list(2, s).unwrap();
+        for (i, v) in [1016, 1017].into_iter().enumerate() {
+            assert!(list.list_set(i as pyt_ssize_t, Owned::from_long(v, s).unwrap(), s));
+        }
+        let mut iter = list.iter(s).unwrap();
+        let mut items = Vec::new();
+        while let Some(item) = iter.next(s) {
+            items.push(item.borrow().as_long(s).unwrap());
+        }
+        assert!(!unsafe { pyt_err_occurred(s) });
+        assert_eq!(items, [1016, 1017]);
+        // an int isn't iterable
+        let n = Owned::from_long(1018, s).unwrap();
+        assert!(n.iter(s).is_none());
+        unsafe { take_error(PytExceptionType::TypeError, s) };
+    });
+}
+
+#[test]
+fn handles_hand_out_the_contents_of_bytes_only() {
+    with_interpreter(|s| {
+        let bytes = Owned::from_bytes(b"spam", s).unwrap();
+        assert_eq!(bytes.borrow().as_bytes(s), Some(&b"spam"[..]));
+        let n = Owned::from_long(1012, s).unwrap();
+        assert_eq!(n.borrow().as_bytes(s), None);
+    });
+}
+
+// the mock hands out the same addresses in every interpreter, so a handle outliving its
+// interpreter would release an object of the next one if it didn't check
+#[test]
+fn handles_outliving_their_interpreter_are_left_alone() {
+    let mut stale = None;
+    with_interpreter(|s| stale = Owned::from_long(1009, s));
+    let stale = stale.unwrap();
+    with_interpreter(|s| {
+        let obj = Owned::from_long(1010, s).unwrap();
+        assert!(obj.ptr() == stale.ptr());
+        drop(stale);
+        assert_eq!(unsafe { pyt_refcnt(obj.ptr(), s) }, 1);
+    });
+}
+
+// the error indicator is per thread: a worker sees none of the main thread's and leaves none behind
+#[test]
+fn worker_thread_has_its_own_error_indicator() {
+    with_interpreter(|s| unsafe {
+        pyt_err_setstring(PytExceptionType::KeyError, cstr(b"main\0"), s);
+        let v = pyt_allow_threads(s, || {
+            thread::spawn(|| {
+                let (gstate, mut s) = pyt_gilstate_ensure();
+                assert!(!pyt_err_occurred(&s));
+                let obj = long(1011, &mut s);
+                let v = pytlong_aslong(obj, &mut s);
+                pyt_decref(obj, &mut s);
+                pyt_err_setstring(PytExceptionType::TypeError, cstr(b"worker\0"), &mut s);
+                pyt_gilstate_release(gstate, s);
+                v
+            }).join().unwrap()
+        });
+        assert_eq!(v, 1011);
+        take_error(PytExceptionType::KeyError, s);
+    });
+}
+
+#[test]
+#[should_panic(expected = "this thread holds the GIL already")]
+fn gilstate_ensure_on_a_thread_holding_the_gil() {
+    with_interpreter(|_| {
+        let _ = pyt_gilstate_ensure();
+    });
+}
@@ -0,0 +1,227 @@
+// Python types implemented in Rust. A type is a Rust value type implementing `PytType`; every
+// instance owns one such value. Each slot the type provides is a trait (`PytGetAttr` for
+// `tp_getattro`, `PytRepr` for `tp_repr`, `PytAdd` for `nb_add`) whose specs are the contract
+// CPython expects from that slot, so an implementation that verifies can be installed as is.
+//
+// `GpyType` in the parent module describes what a type does in the ghost model; the traits here
+// are what actually runs. As with extension functions, CPython calls unverified trampolines that
+// hand the slot a fresh `GpyGlobalState`.
+
+use std::marker::PhantomData;
+
+use prusti_contracts::*;
+
+use super::handle::Borrowed;
+use super::*;
+
+type PytDestructor = unsafe extern "C" fn(PytObjectPointer);
+type PytGetAttroFunc = unsafe extern "C" fn(PytObjectPointer, PytObjectPointer) -> PytObjectPointer;
+type PytReprFunc = unsafe extern "C" fn(PytObjectPointer) -> PytObjectPointer;
+type PytBinaryFunc = unsafe extern "C" fn(PytObjectPointer, PytObjectPointer) -> PytObjectPointer;
+
+extern "C" {
+    fn MyType_FromSlots(name: *const libc::c_char, dealloc: PytDestructor, getattro: Option<PytGetAttroFunc>,
+        repr: Option<PytReprFunc>, nb_add: Option<PytBinaryFunc>) -> PytObjectPointer;
+    fn MyType_Dealloc(typ: PytObjectPointer) -> Option<PytDestructor>;
+    fn MyType_Name(typ: PytObjectPointer) -> *const libc::c_char;
+    fn MyInstance_New(typ: PytObjectPointer, data: *mut libc::c_void) -> PytObjectPointer;
+    fn MyInstance_Data(obj: PytObjectPointer) -> *mut libc::c_void;
+    fn MyInstance_Free(obj: PytObjectPointer);
+}
+
+pub trait PytType: Sized {
+    // the `tp_name`, qualified with the module name (e.g. `rustext.Offset`)
+    const NAME: &'static CStr;
+}
+
+// `tp_getattro`: `obj` is the instance and `this` its value
+pub trait PytGetAttr: PytType {
+    #[requires(gpy_gil_held(1))]
+    #[requires(gpy_ref_held(1, obj))]
+    #[requires(gpy_ref_held(1, name))]
+    #[requires(gpy_no_borrows_active(s))]
+    #[requires(!s.error.is_set())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(gpy_ref_held(1, obj))]
+    #[ensures(gpy_ref_held(1, name))]
+    #[ensures(!result.is_null() ==> gpy_ref_held(1, result) & !s.error.is_set() & refs_held_changed_by(1, old(s), s))]
+    #[ensures(result.is_null() ==> s.error.is_set() && gpy_refs_balanced(old(s), s))]
+    unsafe fn getattr(obj: PytObjectPointer, this: &Self, name: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer;
+}
+
+// `tp_repr`: has to return a str
+pub trait PytRepr: PytType {
+    #[requires(gpy_gil_held(1))]
+    #[requires(gpy_ref_held(1, obj))]
+    #[requires(gpy_no_borrows_active(s))]
+    #[requires(!s.error.is_set())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(gpy_ref_held(1, obj))]
+    #[ensures(!result.is_null() ==> gpy_ref_held(1, result) & pytunicode_checkexact(result, s) & !s.error.is_set() & refs_held_changed_by(1, old(s), s))]
+    #[ensures(result.is_null() ==> s.error.is_set() && gpy_refs_balanced(old(s), s))]
+    unsafe fn repr(obj: PytObjectPointer, this: &Self, s: &mut GpyGlobalState) -> PytObjectPointer;
+}
+
+// `nb_add`: called for `obj + other` and, with `reflected` set, for `other + obj`; operands it
+// doesn't handle should get `pytnotimplemented_get`
+pub trait PytAdd: PytType {
+    #[requires(gpy_gil_held(1))]
+    #[requires(gpy_ref_held(1, obj))]
+    #[requires(gpy_ref_held(1, other))]
+    #[requires(gpy_no_borrows_active(s))]
+    #[requires(!s.error.is_set())]
+    #[ensures(gpy_gil_held(1))]
+    #[ensures(gpy_ref_held(1, obj))]
+    #[ensures(gpy_ref_held(1, other))]
+    #[ensures(!result.is_null() ==> gpy_ref_held(1, result) & !s.error.is_set() & refs_held_changed_by(1, old(s), s))]
+    #[ensures(result.is_null() ==> s.error.is_set() && gpy_refs_balanced(old(s), s))]
+    unsafe fn add(obj: PytObjectPointer, this: &Self, other: PytObjectPointer, reflected: bool, s: &mut GpyGlobalState) -> PytObjectPointer;
+}
+
+// whether `typ` was created by `pyttype_create::<T>`; the name tells apart types whose
+// deallocators happen to be merged into the same code. `PyType_FromSpec` copies the name, so it
+// is compared by contents.
+#[trusted]
+unsafe fn gpy_is_type_for<T: PytType>(typ: PytObjectPointer) -> bool {
+    match MyType_Dealloc(typ) {
+        Some(dealloc) => dealloc as usize == gpy_dealloc::<T> as PytDestructor as usize && CStr::from_ptr(MyType_Name(typ)) == T::NAME,
+        None => false,
+    }
+}
+
+#[trusted]
+unsafe fn gpy_instance_data<'a, T: PytType>(obj: PytObjectPointer) -> Option<&'a T> {
+    if gpy_is_type_for::<T>(My_Type(obj)) {
+        Some(&*(MyInstance_Data(obj) as *const T))
+    } else {
+        None
+    }
+}
+
+#[trusted]
+unsafe extern "C" fn gpy_dealloc<T: PytType>(obj: PytObjectPointer) {
+    drop(Box::from_raw(MyInstance_Data(obj) as *mut T));
+    MyInstance_Free(obj);
+}
+
+#[trusted]
+unsafe extern "C" fn gpy_getattro<T: PytGetAttr>(obj: PytObjectPointer, name: PytObjectPointer) -> PytObjectPointer {
+    let this = &*(MyInstance_Data(obj) as *const T);
+    T::getattr(obj, this, name, &mut gpy_new_state())
+}
+
+#[trusted]
+unsafe extern "C" fn gpy_repr<T: PytRepr>(obj: PytObjectPointer) -> PytObjectPointer {
+    let this = &*(MyInstance_Data(obj) as *const T);
+    T::repr(obj, this, &mut gpy_new_state())
+}
+
+// CPython calls `nb_add` of either operand's type, so `obj0` need not be ours
+#[trusted]
+unsafe extern "C" fn gpy_nb_add<T: PytAdd>(obj0: PytObjectPointer, obj1: PytObjectPointer) -> PytObjectPointer {
+    if let Some(this) = gpy_instance_data::<T>(obj0) {
+        T::add(obj0, this, obj1, false, &mut gpy_new_state())
+    } else if let Some(this) = gpy_instance_data::<T>(obj1) {
+        T::add(obj1, this, obj0, true, &mut gpy_new_state())
+    } else {
+        pytnotimplemented_get(&mut gpy_new_state())
+    }
+}
+
+// the slots to install, e.g. `PytTypeSlots::<Offset>::new().getattro().repr()`
+pub struct PytTypeSlots<T: PytType> {
+    getattro: Option<PytGetAttroFunc>,
+    repr: Option<PytReprFunc>,
+    nb_add: Option<PytBinaryFunc>,
+    _type: PhantomData<T>,
+}
+
+impl<T: PytType> PytTypeSlots<T> {
+    #[trusted]
+    pub const fn new() -> Self {
+        PytTypeSlots { getattro: None, repr: None, nb_add: None, _type: PhantomData }
+    }
+
+    #[trusted]
+    pub const fn getattro(self) -> Self where T: PytGetAttr {
+        PytTypeSlots { getattro: Some(gpy_getattro::<T>), ..self }
+    }
+
+    #[trusted]
+    pub const fn repr(self) -> Self where T: PytRepr {
+        PytTypeSlots { repr: Some(gpy_repr::<T>), ..self }
+    }
+
+    #[trusted]
+    pub const fn nb_add(self) -> Self where T: PytAdd {
+        PytTypeSlots { nb_add: Some(gpy_nb_add::<T>), ..self }
+    }
+}
+
+// a new type object (to be added to a module with `pytmodule_addobject`); its `__mro__` refers
+// back to it, so the caller's reference isn't the only one. Its ghost type is `Generic`: the slots
+// are verified against the specs of their traits, which promise callers no more than running
+// Python code would, so attribute access on instances is specified as doing just that
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(old(s)).ref_count == 0) & (result.gpy_get(s).ref_count >= 1) & all_other_preserved(result, old(s), s) & !result.gpy_get(s).immortal)]
+#[ensures(!result.is_null() ==> result.gpy_get(s).data === GpyObjectData::PyType(GpyTypeImpl::Generic) && result.gpy_get(s).lent == 0)]
+#[ensures(result.is_null() ==> all_objects_preserved(old(s), s))]
+#[ensures(result.is_null() ==> s.error.is_set())]
+#[ensures(!result.is_null() ==> errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pyttype_create<T: PytType>(slots: PytTypeSlots<T>, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let result = MyType_FromSlots(T::NAME.as_ptr(), gpy_dealloc::<T>, slots.getattro, slots.repr, slots.nb_add);
+    #[cfg(feature = "shadow-heap")]
+    s.shadow_new_ref(result, ShadowData::Other, false);
+    result
+}
+
+// a new instance of `typ` holding `value`; fails with a TypeError if `typ` wasn't created for `T`
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, typ))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, typ))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & (result.gpy_get(old(s)).ref_count == 0) & (result.gpy_get(s).ref_count == 1) & !result.gpy_get(s).immortal)]
+#[ensures(!result.is_null() ==> result.gpy_get(s).typ === typ && result.gpy_get(s).lent == 0 && all_other_two_preserved(result, typ, old(s), s))]
+#[ensures(!result.is_null() ==> typ.gpy_get(s).data === typ.gpy_get(old(s)).data && ref_count_changed_by(typ, 1, old(s), s))]
+#[ensures(result.is_null() ==> all_objects_preserved(old(s), s))]
+#[ensures(result.is_null() ==> s.error.is_set())]
+#[ensures(!result.is_null() ==> errors_preserved(old(s), s))]
+#[ensures(constants_preserved(old(s), s))]
+#[ensures(!result.is_null() ==> refs_held_changed_by(1, old(s), s))]
+#[ensures(result.is_null() ==> refs_held_preserved(old(s), s))]
+pub unsafe fn pytinstance_new<T: PytType>(typ: PytObjectPointer, value: T, s: &mut GpyGlobalState) -> PytObjectPointer {
+    if !gpy_is_type_for::<T>(typ) {
+        PyErr_SetString(PyExc_TypeError, b"not a type created for this Rust type\0".as_ptr() as *const libc::c_char);
+        #[cfg(feature = "shadow-heap")]
+        s.shadow_error();
+        return PytObjectPointer { _private: 0 };
+    }
+    #[cfg(feature = "shadow-heap")]
+    let before = s.shadow_use(typ);
+    let data = Box::into_raw(Box::new(value));
+    let obj = MyInstance_New(typ, data as *mut libc::c_void);
+    if obj.is_null() {
+        drop(Box::from_raw(data));
+    }
+    #[cfg(feature = "shadow-heap")]
+    {
+        s.shadow_changed_by(typ, before, if obj.is_null() { 0 } else { 1 }, 0);
+        s.shadow_new_ref(obj, ShadowData::Other, true);
+    }
+    obj
+}
+
+// the value held by `obj` if it is an instance of `T`'s type; like `pytbytes_asslice`, the result
+// lives as long as the borrow of the handle that keeps `obj` alive
+#[trusted]
+#[requires(gpy_gil_held(1))]
+#[ensures(gpy_gil_held(1))]
+pub unsafe fn pytinstance_get<'a, T: PytType>(obj: Borrowed<'a>, s: &GpyGlobalState) -> Option<&'a T> {
+    gpy_instance_data::<T>(obj.ptr())
+}
@@ -0,0 +1,6 @@
+// The verified interface, shared by the embedding binary (src/main.rs) and the extension module.
+
+pub mod interface;
+
+#[cfg(feature = "extension-module")]
+mod rustext;
@@ -1,6 +1,7 @@
-mod interface;
+use std::thread;
 
-use interface::*;
+use rustext::interface::*;
+use rustext::interface::handle::*;
 
 use prusti_contracts::*;
 
@@ -9,19 +10,187 @@ fn pri(v: libc::c_long) {
     println!("{}", v);
 }
 
+// `test2` from c_direct_interaction/example.c, without the binary search: both items are stolen by
+// the list, so decref'ing the list is all the cleanup that's needed
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_no_borrows_active(s))]
+#[ensures(gpy_no_borrows_active(s))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_refs_balanced(old(s), s))]
+unsafe fn test2(s: &mut GpyGlobalState) {
+    let list = pytlist_new(2, s);
+    if list.is_null() {
+        return;
+    }
+
+    let item0 = pytlong_fromlong(23, s);
+    if !item0.is_null() {
+        pytlist_setitem(list, 0, item0, s);
+    }
+    let item1 = pytunicode_fromstring(c"stray cat", s);
+    if !item1.is_null() {
+        pytlist_setitem(list, 1, item1, s);
+    }
+
+    let target = pytlong_fromlong(23, s);
+    pyt_xdecref(target, s);
+
+    pyt_decref(list, s);
+}
+
+// `average_py_longs` from c_direct_interaction/example.c, without building dicts for `PyRun_String`;
+// `a` and `b` may be the same object, as long as the caller holds a reference for each
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_ref_held(1, a))]
+#[requires(gpy_ref_held(1, b))]
+#[requires(pylong_checkexact(a, s) && pylong_checkexact(b, s))]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_ref_held(1, a))]
+#[ensures(gpy_ref_held(1, b))]
+#[ensures(!result.is_null() ==> gpy_ref_held(1, result) & pylong_checkexact(result, s))]
+#[ensures(result.is_null() ==> gpy_refs_balanced(old(s), s))]
+unsafe fn average_py_longs(a: PytObjectPointer, b: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+    let sum = pytnumber_add(a, b, s);
+    if sum.is_null() {
+        return sum;
+    }
+    let two = pytlong_fromlong(2, s);
+    if two.is_null() {
+        pyt_decref(sum, s);
+        return two;
+    }
+    let result = pytnumber_floordivide(sum, two, s);
+    pyt_decref(sum, s);
+    pyt_decref(two, s);
+    result
+}
+
+// part of rust_python_function_call's main, against the verified interface: nothing is known
+// about what `lib` does, so every result has to be checked before it is used
+#[requires(gpy_gil_held(1))]
+#[requires(gpy_no_borrows_active(s))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_no_borrows_active(s))]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_refs_balanced(old(s), s))]
+unsafe fn function_call(s: &mut GpyGlobalState) {
+    pyrun_simplestring(c"import sys\nsys.path.append('')", s);
+
+    let lib_module = pytimport_importmodule(c"lib", s);
+    if lib_module.is_null() {
+        pyt_err_clear(s);
+        return;
+    }
+
+    let get_cubed = pytobject_getattrstring(lib_module, c"get_cubed", s);
+    pyt_decref(lib_module, s);
+    if get_cubed.is_null() {
+        pyt_err_clear(s);
+        return;
+    }
+
+    let seven = pytlong_fromlong(7, s);
+    if seven.is_null() {
+        pyt_err_clear(s);
+        pyt_decref(get_cubed, s);
+        return;
+    }
+    let seven_cubed = pytobject_callonearg(get_cubed, seven, s);
+    pyt_decref(seven, s);
+    pyt_decref(get_cubed, s);
+    if seven_cubed.is_null() {
+        pyt_err_clear(s);
+        return;
+    }
+
+    if pylong_checkexact(seven_cubed, s) {
+        let v = pytlong_aslong(seven_cubed, s);
+        if v == -1 && pyt_err_occurred(s) {
+            pyt_err_clear(s);
+        } else {
+            pri(v);
+        }
+    }
+    pyt_decref(seven_cubed, s);
+}
+
+// the same kind of code as above, but through handles: no `unsafe` and no manual refcounting
+#[requires(gpy_gil_held(1))]
+#[requires(!s.error.is_set())]
+#[ensures(gpy_gil_held(1))]
+#[ensures(gpy_refs_balanced(old(s), s))]
+fn handles(s: &mut GpyGlobalState) {
+    if let Some(mut list) = Owned::new_list(1, s) {
+        if let Some(n) = Owned::from_long(12, s) {
+            list.list_set(0, n, s);
+            if let Some(item) = list.list_get(0, s) {
+                if item.borrow().is_long(s) {
+                    if let Some(v) = item.borrow().as_long(s) {
+                        pri(v);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// a thread Python didn't create takes the GIL, along with a state of its own, while the main thread
+// waits for it with the GIL released
+fn worker_thread(s: &mut GpyGlobalState) {
+    let v = pyt_allow_threads(s, || {
+        thread::spawn(|| {
+            let (gstate, mut s) = pyt_gilstate_ensure();
+            let mut v = -1;
+            unsafe {
+                let n = pytlong_fromlong(42, &mut s);
+                if n.is_null() {
+                    pyt_err_clear(&mut s);
+                } else {
+                    v = pytlong_aslong(n, &mut s);
+                    pyt_decref(n, &mut s);
+                }
+            }
+            pyt_gilstate_release(gstate, s);
+            v
+        }).join().unwrap()
+    });
+    pri(v);
+}
+
 fn main() {
     //prusti_inhale!(gpy_ref_held(1, PytObjectPointer { _private: 0 }));
     //prusti_exhale!(gpy_ref_held(1, PytObjectPointer { _private: 0 }));
+    let mut s = pyt_initialize(PytInterpreter::take().unwrap());
     unsafe {
-        pyt_initialize();
-        let mut s = gpy_create_state();
         let lo = pytlong_fromlong(33, &mut s);
         if !lo.is_null() {
+            let v = pytlong_aslong(lo, &mut s);
+            prusti_assert!(v == 33);
+            prusti_assert!(pyt_is_type(lo, pytlong_gettypenoincref(&s), &s));
+            pyt_incref(lo, &mut s);
+            let avg = average_py_longs(lo, lo, &mut s);
+            pyt_xdecref(avg, &mut s);
+            if pyt_err_occurred(&s) {
+                pyt_err_clear(&mut s);
+            }
+            pyt_decref(lo, &mut s);
             pyt_decref(lo, &mut s);
         }
-        prusti_refute!(false);
-        pyt_finalize();
+        test2(&mut s);
+        if pyt_err_occurred(&s) {
+            pyt_err_clear(&mut s);
+        }
+        function_call(&mut s);
+        if pyt_err_occurred(&s) {
+            pyt_err_clear(&mut s);
+        }
     }
+    handles(&mut s);
+    worker_thread(&mut s);
+    prusti_refute!(false);
+    pyt_finalize(s);
     /*
     unsafe {
         Py_Initialize();
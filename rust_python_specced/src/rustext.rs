@@ -0,0 +1,144 @@
+// An extension module written against the verified interface (the `extension-module` feature).
+// Built with `cargo rustc --lib --crate-type cdylib --features extension-module`; copy
+// target/debug/librustext.so to rustext.so and `import rustext` from Python.
+
+use std::ffi::CStr;
+
+use crate::interface::*;
+use crate::interface::extension::*;
+use crate::interface::types::*;
+
+use prusti_contracts::*;
+
+struct Cube;
+
+#[refine_trait_spec]
+impl PytFunctionO for Cube {
+    unsafe fn call(module: PytObjectPointer, arg: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+        if !pylong_checkexact(arg, s) {
+            pyt_err_setstring(PytExceptionType::TypeError, c"cube() expects an int", s);
+            return PytObjectPointer { _private: 0 };
+        }
+        // one reference per operand
+        pyt_incref(arg, s);
+        let square = pytnumber_multiply(arg, arg, s);
+        pyt_decref(arg, s);
+        if square.is_null() {
+            return square;
+        }
+        let cube = pytnumber_multiply(square, arg, s);
+        pyt_decref(square, s);
+        cube
+    }
+}
+
+// `Offset(by) + n == n + by` for ints `n`
+struct Offset {
+    by: libc::c_long,
+}
+
+impl PytType for Offset {
+    const NAME: &'static CStr = c"rustext.Offset";
+}
+
+// `"Offset({})".format(by)`: Rust's formatting is out of Prusti's reach, Python's goes through the
+// wrappers. Nothing is known about what `format` returns, so it is checked to be a str.
+#[refine_trait_spec]
+impl PytRepr for Offset {
+    unsafe fn repr(obj: PytObjectPointer, this: &Offset, s: &mut GpyGlobalState) -> PytObjectPointer {
+        let template = pytunicode_fromstring(c"Offset({})", s);
+        if template.is_null() {
+            return template;
+        }
+        let format = pytobject_getattrstring(template, c"format", s);
+        pyt_decref(template, s);
+        if format.is_null() {
+            return format;
+        }
+        let by = pytlong_fromlong(this.by, s);
+        if by.is_null() {
+            pyt_decref(format, s);
+            return by;
+        }
+        let result = pytobject_callonearg(format, by, s);
+        pyt_decref(by, s);
+        pyt_decref(format, s);
+        if !result.is_null() && !pytunicode_checkexact(result, s) {
+            pyt_decref(result, s);
+            pyt_err_setstring(PytExceptionType::TypeError, c"str.format() didn't return a str", s);
+            return PytObjectPointer { _private: 0 };
+        }
+        result
+    }
+}
+
+#[refine_trait_spec]
+impl PytAdd for Offset {
+    unsafe fn add(obj: PytObjectPointer, this: &Offset, other: PytObjectPointer, reflected: bool, s: &mut GpyGlobalState) -> PytObjectPointer {
+        if !pylong_checkexact(other, s) {
+            return pytnotimplemented_get(s);
+        }
+        let by = pytlong_fromlong(this.by, s);
+        if by.is_null() {
+            return by;
+        }
+        let result = pytnumber_add(other, by, s);
+        pyt_decref(by, s);
+        result
+    }
+}
+
+// `offset(by)` creates an `Offset`; the type can't be instantiated from Python directly
+struct MakeOffset;
+
+#[refine_trait_spec]
+impl PytFunctionO for MakeOffset {
+    unsafe fn call(module: PytObjectPointer, arg: PytObjectPointer, s: &mut GpyGlobalState) -> PytObjectPointer {
+        if !pylong_checkexact(arg, s) {
+            pyt_err_setstring(PytExceptionType::TypeError, c"offset() expects an int", s);
+            return PytObjectPointer { _private: 0 };
+        }
+        let by = pytlong_aslong(arg, s);
+        if by == -1 && pyt_err_occurred(s) {
+            return PytObjectPointer { _private: 0 };
+        }
+        let typ = pytobject_getattrstring(module, c"Offset", s);
+        if typ.is_null() {
+            return typ;
+        }
+        let result = pytinstance_new(typ, Offset { by }, s);
+        pyt_decref(typ, s);
+        result
+    }
+}
+
+struct RustextExec;
+
+#[refine_trait_spec]
+impl PytModuleExec for RustextExec {
+    unsafe fn exec(module: PytObjectPointer, s: &mut GpyGlobalState) -> bool {
+        let typ = pyttype_create(PytTypeSlots::<Offset>::new().repr().nb_add(), s);
+        if typ.is_null() {
+            return false;
+        }
+        if pytmodule_addobject(module, c"Offset", typ, s) != 0 {
+            pyt_decref(typ, s);
+            return false;
+        }
+        true
+    }
+}
+
+static RUSTEXT_METHODS: [PytMethodDef; 2] = [
+    PytMethodDef::o::<Cube>(c"cube", c"Return x ** 3 for an int x."),
+    PytMethodDef::o::<MakeOffset>(c"offset", c"Return an Offset by the int given."),
+];
+
+// trusted: Prusti can't encode the method table (a static, of structs holding raw and function
+// pointers), and there is nothing to check here anyway: the module goes straight to CPython, and
+// what runs later (the functions and `RustextExec`) is verified against the specs of its trait
+#[trusted]
+#[no_mangle]
+pub unsafe extern "C" fn PyInit_rustext() -> PytObjectPointer {
+    pytmodule_create_exec::<RustextExec>(c"rustext", c"Functions verified with Prusti.", &RUSTEXT_METHODS)
+}